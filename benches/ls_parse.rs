@@ -0,0 +1,85 @@
+//! Compares the `Value` and `RawValue` paths for parsing `ls` responses.
+//!
+//! Run with `cargo bench --features raw-value`.
+
+use kitty_rc::LsCommand;
+use kitty_rc::protocol::KittyResponse;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 50;
+
+/// An `ls` reply for 10 OS windows with 20 tabs of 10 windows each, encoded
+/// the way kitty sends it: as a JSON string inside the response body.
+fn large_fixture() -> String {
+    let mut id = 0u64;
+    let mut next_id = || {
+        id += 1;
+        id
+    };
+
+    let instances: Vec<_> = (0..10)
+        .map(|_| {
+            let tabs: Vec<_> = (0..20)
+                .map(|_| {
+                    let windows: Vec<_> = (0..10)
+                        .map(|_| {
+                            let id = next_id();
+                            serde_json::json!({
+                                "id": id,
+                                "title": format!("window {id}: ~/src/project"),
+                                "pid": 10_000 + id,
+                                "cwd": "/home/user/src/project",
+                                "cmdline": ["/bin/zsh", "-l"],
+                                "env": {"TERM": "xterm-kitty", "SHELL": "/bin/zsh"},
+                                "foreground_processes": [{
+                                    "pid": 20_000 + id,
+                                    "cmdline": ["nvim", "src/main.rs"],
+                                    "cwd": "/home/user/src/project"
+                                }],
+                                "is_focused": false,
+                                "at_prompt": true,
+                                "columns": 120,
+                                "lines": 40
+                            })
+                        })
+                        .collect();
+                    serde_json::json!({
+                        "id": next_id(),
+                        "title": "tab",
+                        "layout": "tall",
+                        "windows": windows
+                    })
+                })
+                .collect();
+            serde_json::json!({"id": next_id(), "is_focused": false, "tabs": tabs})
+        })
+        .collect();
+
+    let data = serde_json::Value::Array(instances).to_string();
+    serde_json::json!({"ok": true, "data": data}).to_string()
+}
+
+fn time(name: &str, mut f: impl FnMut()) {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let per_iter: Duration = start.elapsed() / ITERATIONS;
+    println!("{name:<8} {per_iter:?}/iter");
+}
+
+fn main() {
+    let body = large_fixture();
+    println!("fixture: {} bytes", body.len());
+
+    time("value", || {
+        let response: KittyResponse = serde_json::from_str(black_box(&body)).unwrap();
+        black_box(LsCommand::parse_response(&response).unwrap());
+    });
+
+    time("raw", || {
+        black_box(LsCommand::parse_response_raw(black_box(&body)).unwrap());
+    });
+}
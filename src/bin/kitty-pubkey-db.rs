@@ -1,18 +1,36 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::env;
 use std::fs::{self, File, OpenOptions};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Entry count above which `add` cleans up stale entries early.
+const DEFAULT_MAX_ENTRIES: usize = 1000;
+
+/// Seconds between regular cleanups.
+const CLEANUP_INTERVAL: u64 = 86400;
+
 #[derive(Parser, Debug)]
 #[command(name = "kitty-pubkey-db")]
 #[command(about = "Manage kitty public keys for password authentication", long_about = None)]
 struct Cli {
+    /// Storage format of the database
+    #[arg(long, value_enum, default_value_t = Backend::Tsv, global = true)]
+    backend: Backend,
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Backend {
+    /// Tab-separated text file (pubkey.tsv)
+    Tsv,
+    /// SQLite database (pubkey.sqlite), safe for concurrent writers
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Initialize the database
@@ -28,6 +46,10 @@ enum Commands {
         /// Window ID (optional, reads from KITTY_WINDOW_ID if not provided)
         #[arg(long)]
         window_id: Option<u32>,
+        /// Clean up once the database holds more entries than this, in
+        /// addition to the daily cleanup
+        #[arg(long, default_value_t = DEFAULT_MAX_ENTRIES)]
+        max_entries: usize,
     },
     /// Clean up stale entries
     Cleanup,
@@ -35,21 +57,35 @@ enum Commands {
     Get {
         /// PID of the kitty instance
         pid: u32,
+        /// Print `pubkey<TAB>window_id<TAB>age_seconds`
+        #[arg(long, conflicts_with = "json")]
+        verbose: bool,
+        /// Print the entry as a JSON object
+        #[arg(long)]
+        json: bool,
     },
+    /// List all entries
+    List,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
-    match cli.command {
-        Commands::Init => init()?,
-        Commands::Add {
-            pid,
-            window_id,
-            pubkey,
-        } => add(pid, window_id, pubkey)?,
-        Commands::Cleanup => cleanup()?,
-        Commands::Get { pid } => get(pid)?,
+    match cli.backend {
+        Backend::Tsv => match cli.command {
+            Commands::Init => init()?,
+            Commands::Add {
+                pid,
+                window_id,
+                pubkey,
+                max_entries,
+            } => add(pid, window_id, pubkey, max_entries)?,
+            Commands::Cleanup => cleanup()?,
+            Commands::Get { pid, verbose, json } => get(pid, GetFormat::new(verbose, json))?,
+            Commands::List => list()?,
+        },
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => sqlite::run(cli.command)?,
     }
 
     Ok(())
@@ -88,8 +124,42 @@ fn add(
     pid: Option<u32>,
     window_id: Option<u32>,
     pubkey: Option<String>,
+    max_entries: usize,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Read from environment if not provided
+    let entry = resolve_entry(pid, window_id, pubkey)?;
+    let db_path = get_db_path()?;
+
+    append_entry(&db_path, &entry)?;
+
+    if let Some((kept, total)) =
+        check_and_cleanup_if_needed(&db_path, &get_epoch_path()?, max_entries)?
+    {
+        println!("Cleanup complete: kept {} of {} entries", kept, total);
+    }
+
+    Ok(())
+}
+
+fn append_entry(db_path: &Path, entry: &DbEntry) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = OpenOptions::new().append(true).create(true).open(db_path)?;
+
+    writeln!(
+        file,
+        "{}\t{}\t{}\t{}",
+        entry.pid, entry.window_id, entry.pubkey, entry.timestamp
+    )?;
+    file.flush()?;
+
+    Ok(())
+}
+
+/// Build the entry for `add`, reading anything not given on the command
+/// line from the environment kitty sets up for its children.
+fn resolve_entry(
+    pid: Option<u32>,
+    window_id: Option<u32>,
+    pubkey: Option<String>,
+) -> Result<DbEntry, Box<dyn std::error::Error>> {
     let pid = pid
         .or_else(|| std::env::var("KITTY_PID").ok().and_then(|s| s.parse().ok()))
         .ok_or_else(|| {
@@ -108,10 +178,9 @@ fn add(
             )
         })?;
 
-    let db_path = get_db_path()?;
     let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
 
-    let window_id_str = window_id
+    let window_id = window_id
         .or_else(|| {
             std::env::var("KITTY_WINDOW_ID")
                 .ok()
@@ -120,27 +189,18 @@ fn add(
         .map(|id| id.to_string())
         .unwrap_or_else(|| "".to_string());
 
-    let entry = format!("{}\t{}\t{}\t{}", pid, window_id_str, pubkey, timestamp);
-
-    let mut file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(&db_path)?;
-
-    writeln!(file, "{}", entry)?;
-    file.flush()?;
-
-    check_and_cleanup_if_needed(&db_path)?;
-
-    Ok(())
+    Ok(DbEntry {
+        pid,
+        window_id,
+        pubkey,
+        timestamp,
+    })
 }
 
 fn cleanup() -> Result<(), Box<dyn std::error::Error>> {
     let db_path = get_db_path()?;
     let epoch_path = get_epoch_path()?;
 
-    let _current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-
     write_current_time(&epoch_path)?;
 
     if !db_path.exists() {
@@ -148,7 +208,16 @@ fn cleanup() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
-    let content = fs::read_to_string(&db_path)?;
+    let (kept, total) = cleanup_file(&db_path)?;
+    println!("Cleanup complete: kept {} of {} entries", kept, total);
+
+    Ok(())
+}
+
+/// Drop entries of processes that are gone from the TSV at `db_path`.
+/// Returns the number of entries kept and the number before.
+fn cleanup_file(db_path: &Path) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(db_path)?;
     let entries: Vec<DbEntry> = content
         .lines()
         .filter(|line| !line.is_empty())
@@ -161,7 +230,7 @@ fn cleanup() -> Result<(), Box<dyn std::error::Error>> {
         .cloned()
         .collect();
 
-    let mut file = File::create(&db_path)?;
+    let mut file = File::create(db_path)?;
     for entry in &alive_entries {
         writeln!(
             file,
@@ -171,16 +240,46 @@ fn cleanup() -> Result<(), Box<dyn std::error::Error>> {
     }
     file.flush()?;
 
-    println!(
-        "Cleanup complete: kept {} of {} entries",
-        alive_entries.len(),
-        entries.len()
-    );
+    Ok((alive_entries.len(), entries.len()))
+}
 
-    Ok(())
+/// Output of the `get` subcommand.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GetFormat {
+    Pubkey,
+    Verbose,
+    Json,
 }
 
-fn get(pid: u32) -> Result<(), Box<dyn std::error::Error>> {
+impl GetFormat {
+    fn new(verbose: bool, json: bool) -> Self {
+        if json {
+            Self::Json
+        } else if verbose {
+            Self::Verbose
+        } else {
+            Self::Pubkey
+        }
+    }
+
+    fn render(self, entry: &DbEntry, now: u64) -> String {
+        let age = now.saturating_sub(entry.timestamp);
+        match self {
+            Self::Pubkey => entry.pubkey.clone(),
+            Self::Verbose => format!("{}\t{}\t{}", entry.pubkey, entry.window_id, age),
+            Self::Json => serde_json::json!({
+                "pid": entry.pid,
+                "pubkey": entry.pubkey,
+                "window_id": entry.window_id.parse::<u64>().ok(),
+                "timestamp": entry.timestamp,
+                "age_seconds": age,
+            })
+            .to_string(),
+        }
+    }
+}
+
+fn get(pid: u32, format: GetFormat) -> Result<(), Box<dyn std::error::Error>> {
     let db_path = get_db_path()?;
 
     if !db_path.exists() {
@@ -189,15 +288,9 @@ fn get(pid: u32) -> Result<(), Box<dyn std::error::Error>> {
     }
 
     let content = fs::read_to_string(&db_path)?;
-    let entries: Vec<DbEntry> = content
-        .lines()
-        .filter(|line| !line.is_empty())
-        .filter_map(|line| parse_db_entry(line))
-        .filter(|entry| entry.pid == pid)
-        .collect();
 
-    if let Some(entry) = entries.into_iter().max_by_key(|e| e.timestamp) {
-        println!("{}", entry.pubkey);
+    if let Some(entry) = latest_entry(&content, pid) {
+        println!("{}", format.render(&entry, now()?));
     } else {
         eprintln!("No public key found for PID {}", pid);
         std::process::exit(1);
@@ -206,6 +299,43 @@ fn get(pid: u32) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// The most recently added entry for `pid` in the TSV `content`.
+fn latest_entry(content: &str, pid: u32) -> Option<DbEntry> {
+    content
+        .lines()
+        .filter(|line| !line.is_empty())
+        .filter_map(parse_db_entry)
+        .filter(|entry| entry.pid == pid)
+        .max_by_key(|e| e.timestamp)
+}
+
+fn now() -> Result<u64, Box<dyn std::error::Error>> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+fn list() -> Result<(), Box<dyn std::error::Error>> {
+    let db_path = get_db_path()?;
+
+    if !db_path.exists() {
+        eprintln!("Database does not exist");
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(&db_path)?;
+    for entry in content.lines().filter_map(parse_db_entry) {
+        print_entry(&entry);
+    }
+
+    Ok(())
+}
+
+fn print_entry(entry: &DbEntry) {
+    println!(
+        "{}\t{}\t{}\t{}",
+        entry.pid, entry.window_id, entry.pubkey, entry.timestamp
+    );
+}
+
 fn get_db_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
     let home = env::var("HOME").unwrap_or_else(|_| ".".to_string());
     let xdg_state = env::var("XDG_STATE_HOME").unwrap_or(format!("{}/.local/state", home));
@@ -229,23 +359,40 @@ fn write_current_time(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn check_and_cleanup_if_needed(_db_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    let epoch_path = get_epoch_path()?;
+/// Clean up the TSV at `db_path` when the regular cleanup is due or it
+/// holds more than `max_entries` entries, so a burst of shells can't bloat
+/// it between cleanups. Returns the cleanup counts if it ran.
+fn check_and_cleanup_if_needed(
+    db_path: &Path,
+    epoch_path: &Path,
+    max_entries: usize,
+) -> Result<Option<(usize, usize)>, Box<dyn std::error::Error>> {
+    let over_capacity = || -> Result<bool, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(db_path)?;
+        Ok(content.lines().filter(|line| !line.is_empty()).count() > max_entries)
+    };
+
+    if !check_cleanup_due(epoch_path)? && !over_capacity()? {
+        return Ok(None);
+    }
+
+    write_current_time(epoch_path)?;
+    Ok(Some(cleanup_file(db_path)?))
+}
 
+/// Whether a day has passed since the last cleanup. Starts the clock if
+/// it isn't running yet.
+fn check_cleanup_due(epoch_path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
     if !epoch_path.exists() {
-        write_current_time(&epoch_path)?;
-        return Ok(());
+        write_current_time(epoch_path)?;
+        return Ok(false);
     }
 
     let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
 
-    let last_cleanup = fs::read_to_string(&epoch_path)?.trim().parse::<u64>()?;
-
-    if current_time - last_cleanup >= 86400 {
-        cleanup()?;
-    }
+    let last_cleanup = fs::read_to_string(epoch_path)?.trim().parse::<u64>()?;
 
-    Ok(())
+    Ok(current_time - last_cleanup >= CLEANUP_INTERVAL)
 }
 
 fn is_process_running(pid: u32) -> bool {
@@ -290,6 +437,160 @@ fn parse_db_entry(line: &str) -> Option<DbEntry> {
     })
 }
 
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::{
+        Commands, DbEntry, GetFormat, check_cleanup_due, get_db_dir, get_epoch_path,
+        is_process_running, now, print_entry, resolve_entry, write_current_time,
+    };
+    use rusqlite::{Connection, params};
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    const SCHEMA_VERSION: i32 = 1;
+
+    pub fn run(command: Commands) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(get_db_dir()?)?;
+        let db = SqliteDb::open(&get_db_path()?)?;
+
+        match command {
+            Commands::Init => {
+                println!("Database ready: {}", get_db_path()?.display());
+                let epoch_path = get_epoch_path()?;
+                if !epoch_path.exists() {
+                    write_current_time(&epoch_path)?;
+                }
+            }
+            Commands::Add {
+                pid,
+                window_id,
+                pubkey,
+                max_entries,
+            } => {
+                db.add(&resolve_entry(pid, window_id, pubkey)?)?;
+                if check_cleanup_due(&get_epoch_path()?)? || db.count()? > max_entries {
+                    write_current_time(&get_epoch_path()?)?;
+                    db.cleanup(is_process_running)?;
+                }
+            }
+            Commands::Cleanup => {
+                write_current_time(&get_epoch_path()?)?;
+                let (kept, total) = db.cleanup(is_process_running)?;
+                println!("Cleanup complete: kept {} of {} entries", kept, total);
+            }
+            Commands::Get { pid, verbose, json } => match db.get(pid)? {
+                Some(entry) => println!("{}", GetFormat::new(verbose, json).render(&entry, now()?)),
+                None => {
+                    eprintln!("No public key found for PID {}", pid);
+                    std::process::exit(1);
+                }
+            },
+            Commands::List => {
+                for entry in db.list()? {
+                    print_entry(&entry);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_db_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+        Ok(get_db_dir()?.join("pubkey.sqlite"))
+    }
+
+    /// Entries keyed on `(pid, window_id)`; the primary key also serves
+    /// lookups by pid. The schema version lives in `user_version`.
+    pub struct SqliteDb {
+        conn: Connection,
+    }
+
+    impl SqliteDb {
+        pub fn open(path: &Path) -> rusqlite::Result<Self> {
+            let conn = Connection::open(path)?;
+            conn.busy_timeout(Duration::from_secs(5))?;
+            conn.pragma_update(None, "journal_mode", "WAL")?;
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS entries (
+                    pid INTEGER NOT NULL,
+                    window_id TEXT NOT NULL,
+                    pubkey TEXT NOT NULL,
+                    timestamp INTEGER NOT NULL,
+                    PRIMARY KEY (pid, window_id)
+                )",
+            )?;
+            conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            Ok(Self { conn })
+        }
+
+        /// Insert the entry, replacing the key and timestamp of an existing
+        /// entry for the same window.
+        pub fn add(&self, entry: &DbEntry) -> rusqlite::Result<()> {
+            self.conn.execute(
+                "INSERT INTO entries (pid, window_id, pubkey, timestamp)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (pid, window_id)
+                 DO UPDATE SET pubkey = excluded.pubkey, timestamp = excluded.timestamp",
+                params![entry.pid, entry.window_id, entry.pubkey, entry.timestamp],
+            )?;
+            Ok(())
+        }
+
+        /// The most recently added entry for `pid`.
+        pub fn get(&self, pid: u32) -> rusqlite::Result<Option<DbEntry>> {
+            let mut stmt = self.conn.prepare(
+                "SELECT pid, window_id, pubkey, timestamp FROM entries
+                 WHERE pid = ?1 ORDER BY timestamp DESC LIMIT 1",
+            )?;
+            let mut rows = stmt.query_map([pid], row_to_entry)?;
+            rows.next().transpose()
+        }
+
+        pub fn count(&self) -> rusqlite::Result<usize> {
+            self.conn
+                .query_row("SELECT COUNT(*) FROM entries", [], |row| row.get(0))
+        }
+
+        pub fn list(&self) -> rusqlite::Result<Vec<DbEntry>> {
+            let mut stmt = self.conn.prepare(
+                "SELECT pid, window_id, pubkey, timestamp FROM entries
+                 ORDER BY pid, timestamp",
+            )?;
+            stmt.query_map([], row_to_entry)?.collect()
+        }
+
+        /// Delete entries of processes for which `is_alive` is false.
+        /// Returns the number of entries kept and the number before.
+        pub fn cleanup(&self, is_alive: impl Fn(u32) -> bool) -> rusqlite::Result<(usize, usize)> {
+            let tx = self.conn.unchecked_transaction()?;
+            let total: usize =
+                tx.query_row("SELECT COUNT(*) FROM entries", [], |row| row.get(0))?;
+
+            let pids: Vec<u32> = tx
+                .prepare("SELECT DISTINCT pid FROM entries")?
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+
+            let mut removed = 0;
+            for pid in pids.into_iter().filter(|pid| !is_alive(*pid)) {
+                removed += tx.execute("DELETE FROM entries WHERE pid = ?1", [pid])?;
+            }
+
+            tx.commit()?;
+            Ok((total - removed, total))
+        }
+    }
+
+    fn row_to_entry(row: &rusqlite::Row) -> rusqlite::Result<DbEntry> {
+        Ok(DbEntry {
+            pid: row.get(0)?,
+            window_id: row.get(1)?,
+            pubkey: row.get(2)?,
+            timestamp: row.get(3)?,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -314,6 +615,79 @@ mod tests {
         assert_eq!(entry.timestamp, 1704067200);
     }
 
+    #[test]
+    fn test_get_formats() {
+        let db = "12345\t7\t1:old\t1000\n\
+                  12345\t9\t1:new\t1900\n\
+                  555\t\t1:other\t1950\n";
+        let entry = latest_entry(db, 12345).unwrap();
+
+        assert_eq!(GetFormat::Pubkey.render(&entry, 2000), "1:new");
+        assert_eq!(GetFormat::Verbose.render(&entry, 2000), "1:new\t9\t100");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&GetFormat::Json.render(&entry, 2000)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "pid": 12345,
+                "pubkey": "1:new",
+                "window_id": 9,
+                "timestamp": 1900,
+                "age_seconds": 100
+            })
+        );
+
+        let other = latest_entry(db, 555).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&GetFormat::Json.render(&other, 2000)).unwrap();
+        assert!(json["window_id"].is_null());
+        assert!(latest_entry(db, 1).is_none());
+    }
+
+    #[test]
+    fn test_cleanup_on_entry_count() {
+        let dir = std::env::temp_dir().join(format!("kitty-pubkey-db-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("pubkey.tsv");
+        let epoch_path = dir.join("pubkey-check.epoch");
+        let _ = fs::remove_file(&db_path);
+        // a fresh epoch, so only the entry count can trigger cleanup
+        write_current_time(&epoch_path).unwrap();
+
+        let alive = std::process::id();
+        let dead = u32::MAX;
+        let entry = |pid: u32, window_id: u32| DbEntry {
+            pid,
+            window_id: window_id.to_string(),
+            pubkey: "1:key".to_string(),
+            timestamp: 1,
+        };
+
+        for (n, pid) in [dead, alive, dead].into_iter().enumerate() {
+            append_entry(&db_path, &entry(pid, n as u32)).unwrap();
+            assert_eq!(
+                check_and_cleanup_if_needed(&db_path, &epoch_path, 3).unwrap(),
+                None
+            );
+        }
+
+        append_entry(&db_path, &entry(alive, 3)).unwrap();
+        assert_eq!(
+            check_and_cleanup_if_needed(&db_path, &epoch_path, 3).unwrap(),
+            Some((2, 4))
+        );
+
+        let content = fs::read_to_string(&db_path).unwrap();
+        let pids: Vec<u32> = content
+            .lines()
+            .filter_map(parse_db_entry)
+            .map(|entry| entry.pid)
+            .collect();
+        assert_eq!(pids, vec![alive, alive]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_parse_db_entry_no_timestamp() {
         let line = "12345\t67890\t1:abc123";
@@ -323,4 +697,75 @@ mod tests {
         assert_eq!(entry.pubkey, "1:abc123");
         assert_eq!(entry.timestamp, 0);
     }
+
+    #[cfg(feature = "sqlite")]
+    fn temp_sqlite_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "kitty-pubkey-db-test-{}-{}.sqlite",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn entry(pid: u32, window_id: &str, pubkey: &str, timestamp: u64) -> DbEntry {
+        DbEntry {
+            pid,
+            window_id: window_id.to_string(),
+            pubkey: pubkey.to_string(),
+            timestamp,
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_concurrent_adds() {
+        let path = temp_sqlite_path("concurrent");
+        sqlite::SqliteDb::open(&path).unwrap();
+
+        let writers: Vec<_> = (0..8u32)
+            .map(|writer| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let db = sqlite::SqliteDb::open(&path).unwrap();
+                    for n in 0..25u64 {
+                        let window_id = (writer % 2).to_string();
+                        let pubkey = format!("1:key-{}-{}", writer, n);
+                        db.add(&entry(100 + writer / 2, &window_id, &pubkey, n))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let db = sqlite::SqliteDb::open(&path).unwrap();
+        let entries = db.list().unwrap();
+        // one row per (pid, window_id) despite 200 adds
+        assert_eq!(entries.len(), 8);
+        assert!(entries.iter().all(|entry| entry.timestamp == 24));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_get_and_cleanup() {
+        let path = temp_sqlite_path("get");
+        let db = sqlite::SqliteDb::open(&path).unwrap();
+
+        db.add(&entry(42, "1", "1:old", 10)).unwrap();
+        db.add(&entry(42, "2", "1:new", 20)).unwrap();
+        db.add(&entry(7, "", "1:dead", 30)).unwrap();
+
+        assert_eq!(db.get(42).unwrap().unwrap().pubkey, "1:new");
+        assert!(db.get(1).unwrap().is_none());
+
+        assert_eq!(db.cleanup(|pid| pid == 42).unwrap(), (2, 3));
+        assert!(db.get(7).unwrap().is_none());
+        let _ = fs::remove_file(&path);
+    }
 }
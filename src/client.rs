@@ -1,27 +1,250 @@
+use crate::command::CommandBuilder;
+use crate::commands::action::{
+    ActionCommand, GotoTabAction, NthWindowAction, ResetWindowSizesAction,
+};
+use crate::commands::config::DebugConfigCommand;
+use crate::commands::layout::GotoLayoutCommand;
+use crate::commands::process::SetUserVarsCommand;
+use crate::commands::style::FontInfo;
+use crate::commands::tab::{DetachTabCommand, FocusTabCommand};
+use crate::commands::window::{
+    CreateMarkerCommand, DetachWindowCommand, FocusWindowCommand, GetTextCommand, LsCommand,
+    OsInstance, RemoveMarkerCommand, SendTextCommand, SetWindowTitleCommand, TabInfo, WindowInfo,
+    active_window, all_windows, all_windows_with_ids,
+};
 use crate::encryption::Encryptor;
-use crate::error::{ConnectionError, EncryptionError, KittyError};
-use crate::protocol::{KittyMessage, KittyResponse};
-use std::path::Path;
+use crate::error::{CommandError, ConnectionError, EncryptionError, KittyError, ProtocolError};
+use crate::ids::{OsWindowId, TabId, WindowId};
+use crate::match_spec::MatchSpec;
+use crate::protocol::{KittyMessage, KittyResponse, ResponseKind};
+use futures_sink::Sink;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use tokio::time::timeout;
+use tokio_util::sync::{CancellationToken, PollSendError, PollSender};
 use xdg::BaseDirectories;
 
+/// What [`Kitty::capture_to_file`] captures.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CaptureOptions {
+    /// Keep formatting as ANSI escape codes.
+    pub ansi: bool,
+    /// kitty's `extent`, e.g. `screen` (the default), `all` or
+    /// `last_cmd_output`.
+    pub extent: Option<String>,
+}
+
+/// How [`Kitty::wait_for_exit`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitOutcome {
+    /// The process is no longer among the window's foreground processes.
+    Exited,
+    /// The window closed, which kitty does when its process exits unless
+    /// it was launched with `--hold`.
+    WindowClosed,
+    /// The timeout elapsed with the process still running.
+    TimedOut,
+    /// The caller's [`CancellationToken`] fired, see
+    /// [`Kitty::wait_for_exit_cancellable`].
+    Cancelled,
+}
+
+/// Default for [`KittyBuilder::poll_interval`].
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How many items [`Kitty::text_sink`] buffers before applying backpressure.
+pub const TEXT_SINK_CAPACITY: usize = 32;
+
+/// Helper queried for kitty's public key, see [`KittyBuilder::connect`].
+const PUBKEY_DB_PROGRAM: &str = "kitty-pubkey-db";
+
+/// How long a public key found in the pubkey database is reused for
+/// further connections to the same kitty instance.
+pub const PUBKEY_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Public keys from the pubkey database by kitty pid, with when they were
+/// looked up.
+static PUBKEY_CACHE: LazyLock<Mutex<HashMap<u32, (Instant, String)>>> =
+    LazyLock::new(Default::default);
+
+/// Forget all cached public keys, e.g. after kitty was restarted with the
+/// same pid or its key changed.
+pub fn clear_pubkey_cache() {
+    PUBKEY_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clear();
+}
+
+/// Default cap on the size of a single response, see
+/// [`KittyBuilder::max_response_bytes`].
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 64 * 1024 * 1024;
+
 pub struct Kitty {
     stream: UnixStream,
     timeout: Duration,
+    interactive_timeout: Option<Duration>,
+    poll_interval: Duration,
+    max_response_bytes: usize,
     socket_path: String,
     password: Option<String>,
     encryptor: Option<Encryptor>,
+    on_reconnect: Option<ReconnectHook>,
+    on_progress: Option<ProgressHook>,
+}
+
+/// A [`Kitty`] shared between tasks, see [`Kitty::into_shared`].
+pub type SharedKitty = Arc<tokio::sync::Mutex<Kitty>>;
+
+/// Callback run after every successful [`Kitty::reconnect`].
+type ReconnectHook = Arc<dyn Fn() + Send + Sync>;
+
+/// Callback run for interim response envelopes, see
+/// [`KittyBuilder::on_progress`].
+type ProgressHook = Arc<dyn Fn(ResponseKind, &KittyResponse) + Send + Sync>;
+
+impl fmt::Debug for Kitty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Kitty")
+            .field("socket_path", &self.socket_path)
+            .field("timeout", &self.timeout)
+            .field("interactive_timeout", &self.interactive_timeout)
+            .field("poll_interval", &self.poll_interval)
+            .field("max_response_bytes", &self.max_response_bytes)
+            .field("password", &self.password.as_ref().map(|_| REDACTED))
+            .field("encryptor", &self.encryptor.as_ref().map(|_| REDACTED))
+            .field("on_reconnect", &self.on_reconnect.is_some())
+            .field("on_progress", &self.on_progress.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Wall-clock timings for a single request/response round trip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecTiming {
+    /// Time spent encrypting, encoding and writing the request.
+    pub send: Duration,
+    /// Time spent waiting for and reading the response.
+    pub wait: Duration,
+    pub total: Duration,
+}
+
+/// Environment variable conventionally used with
+/// [`KittyBuilder::password_env`].
+pub const DEFAULT_PASSWORD_ENV: &str = "KITTY_RC_PASSWORD";
+
+/// Where [`KittyBuilder`] gets the password from. Resolved at connect time.
+#[derive(Clone, PartialEq, Eq)]
+enum PasswordSource {
+    Inline(String),
+    File(PathBuf),
+    Env(String),
+}
+
+/// Part of the error kitty can answer a streamed command with when the
+/// stream broke off; resending the whole command usually succeeds.
+const STREAM_CLOSED_ERROR: &str = "stream closed";
+
+/// Stands in for secrets in `Debug` output.
+const REDACTED: &str = "***";
+
+impl fmt::Debug for PasswordSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordSource::Inline(_) => f.debug_tuple("Inline").field(&REDACTED).finish(),
+            PasswordSource::File(path) => f.debug_tuple("File").field(path).finish(),
+            PasswordSource::Env(var) => f.debug_tuple("Env").field(var).finish(),
+        }
+    }
+}
+
+impl PasswordSource {
+    fn resolve(self) -> Result<String, EncryptionError> {
+        match self {
+            PasswordSource::Inline(password) => Ok(password),
+            PasswordSource::File(path) => std::fs::read_to_string(&path)
+                .map(|contents| contents.trim().to_string())
+                .map_err(|e| {
+                    EncryptionError::PasswordUnavailable(format!(
+                        "failed to read {}: {}",
+                        path.display(),
+                        e
+                    ))
+                }),
+            PasswordSource::Env(var) => std::env::var(&var).map_err(|_| {
+                EncryptionError::PasswordUnavailable(format!(
+                    "environment variable {} is not set",
+                    var
+                ))
+            }),
+        }
+    }
+}
+
+/// Whether a kitty instance accepts remote control, as classified by
+/// [`Kitty::check_remote_control`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RcStatus {
+    Enabled,
+    /// kitty wants a password, see [`KittyBuilder::password`].
+    PasswordRequired,
+    /// kitty is asking the user to confirm the request.
+    AskPending,
+    Disabled,
+}
+
+impl RcStatus {
+    /// Classify the reply to a trivial command.
+    pub fn from_response(response: &KittyResponse) -> Self {
+        if response.ok {
+            return RcStatus::Enabled;
+        }
+
+        let error = response.error.as_deref().unwrap_or_default().to_lowercase();
+        if error.contains("password") {
+            RcStatus::PasswordRequired
+        } else if error.contains("waiting for") || error.contains("confirm") {
+            RcStatus::AskPending
+        } else {
+            RcStatus::Disabled
+        }
+    }
 }
 
 pub struct KittyBuilder {
     socket_path: Option<String>,
-    password: Option<String>,
+    password: Option<PasswordSource>,
     public_key: Option<String>,
     timeout: Duration,
+    interactive_timeout: Option<Duration>,
+    poll_interval: Duration,
+    max_response_bytes: usize,
+    on_reconnect: Option<ReconnectHook>,
+    on_progress: Option<ProgressHook>,
+}
+
+impl fmt::Debug for KittyBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KittyBuilder")
+            .field("socket_path", &self.socket_path)
+            .field("password", &self.password)
+            .field("public_key", &self.public_key)
+            .field("timeout", &self.timeout)
+            .field("interactive_timeout", &self.interactive_timeout)
+            .field("poll_interval", &self.poll_interval)
+            .field("max_response_bytes", &self.max_response_bytes)
+            .field("on_reconnect", &self.on_reconnect.is_some())
+            .field("on_progress", &self.on_progress.is_some())
+            .finish()
+    }
 }
 
 impl KittyBuilder {
@@ -31,29 +254,94 @@ impl KittyBuilder {
             password: None,
             public_key: None,
             timeout: Duration::from_secs(10),
+            interactive_timeout: None,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            on_reconnect: None,
+            on_progress: None,
         }
     }
 
+    /// The kitty pid from a socket named `<name>-<pid>.sock`, or
+    /// `<name>-<pid>` as kitty names sockets when it appends the pid to a
+    /// `listen_on` path. Name-only sockets such as `kitty.sock` give `None`.
     fn extract_pid_from_socket(socket_path: &str) -> Option<u32> {
         let filename = Path::new(socket_path)
             .file_name()?
             .to_str()?;
 
-        let pid_str = filename.strip_prefix("kitty-")?;
-        let pid_str = pid_str.strip_suffix(".sock")?;
+        let stem = filename.strip_suffix(".sock").unwrap_or(filename);
+        let (name, pid_str) = stem.rsplit_once('-')?;
+        if name.is_empty() {
+            return None;
+        }
         pid_str.parse().ok()
     }
 
-    fn query_public_key_database(pid: u32) -> Result<Option<String>, EncryptionError> {
-        let output = Command::new("kitty-pubkey-db")
+    /// Pick the public key to encrypt with, in the order documented on
+    /// [`connect`](Self::connect). `None` leaves it to `KITTY_PUBLIC_KEY`.
+    /// Keys found by `lookup` are cached per pid, see [`PUBKEY_CACHE_TTL`].
+    fn resolve_public_key(
+        explicit: Option<String>,
+        socket_path: &str,
+        lookup: impl FnOnce(u32) -> Result<Option<String>, EncryptionError>,
+    ) -> Result<Option<String>, EncryptionError> {
+        if explicit.is_some() {
+            return Ok(explicit);
+        }
+
+        let Some(pid) = Self::extract_pid_from_socket(socket_path) else {
+            return Ok(None);
+        };
+
+        let mut cache = PUBKEY_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((resolved_at, key)) = cache.get(&pid)
+            && resolved_at.elapsed() < PUBKEY_CACHE_TTL
+        {
+            return Ok(Some(key.clone()));
+        }
+
+        let key = lookup(pid)?;
+        match &key {
+            Some(key) => cache.insert(pid, (Instant::now(), key.clone())),
+            None => cache.remove(&pid),
+        };
+        Ok(key)
+    }
+
+    /// Look up kitty's public key for `pid` with the `pubkey_db` helper.
+    /// A helper that is not installed is treated as having no entry so
+    /// resolution falls through to the environment; any other failure to
+    /// run it is an error.
+    fn query_public_key_database(
+        pubkey_db: &str,
+        pid: u32,
+    ) -> Result<Option<String>, EncryptionError> {
+        let output = match Command::new(pubkey_db)
             .arg("get")
             .arg(pid.to_string())
             .output()
-            .map_err(|e| {
-                EncryptionError::PublicKeyDatabaseError(format!("Failed to run kitty-pubkey-db: {}", e))
-            })?;
+        {
+            Ok(output) => output,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                log::debug!("{} not found, falling back to KITTY_PUBLIC_KEY", pubkey_db);
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(EncryptionError::PublicKeyDatabaseError(format!(
+                    "Failed to run {}: {}",
+                    pubkey_db, e
+                )));
+            }
+        };
 
         if !output.status.success() {
+            log::debug!(
+                "{} has no key for pid {} ({}), falling back to KITTY_PUBLIC_KEY",
+                pubkey_db,
+                pid,
+                output.status
+            );
             return Ok(None);
         }
 
@@ -90,8 +378,72 @@ impl KittyBuilder {
         self
     }
 
+    /// Limit how long to wait for the response to an interactive command,
+    /// i.e. one marked with [`KittyMessage::interactive`] such as
+    /// `select-window`, which blocks until the user picks something. By
+    /// default these wait indefinitely rather than using
+    /// [`timeout`](Self::timeout).
+    pub fn interactive_timeout(mut self, duration: Duration) -> Self {
+        self.interactive_timeout = Some(duration);
+        self
+    }
+
+    /// How often [`Kitty::wait_for_exit`] polls `ls`. Defaults to
+    /// [`DEFAULT_POLL_INTERVAL`].
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Abort reading a response once it grows past `bytes`, protecting
+    /// against unbounded memory use (e.g. `get-text` on a huge scrollback).
+    /// Defaults to [`DEFAULT_MAX_RESPONSE_BYTES`].
+    pub fn max_response_bytes(mut self, bytes: usize) -> Self {
+        self.max_response_bytes = bytes;
+        self
+    }
+
+    /// Run `callback` after each successful [`Kitty::reconnect`], including
+    /// the automatic reconnects done by [`Kitty::text_sink`], e.g. to set
+    /// up state again on the fresh connection.
+    pub fn on_reconnect(mut self, callback: impl Fn() + Send + Sync + 'static) -> Self {
+        self.on_reconnect = Some(Arc::new(callback));
+        self
+    }
+
+    /// Run `callback` for every [`ResponseKind::Progress`] or
+    /// [`ResponseKind::Ack`] envelope kitty sends ahead of a command's
+    /// result. Such envelopes are skipped either way; responses are only
+    /// ever the [`ResponseKind::Final`] one.
+    pub fn on_progress(
+        mut self,
+        callback: impl Fn(ResponseKind, &KittyResponse) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Authenticate with `password`. This and the other password sources
+    /// ([`password_file`](Self::password_file),
+    /// [`password_env`](Self::password_env)) replace each other; the last
+    /// one set wins.
     pub fn password(mut self, password: impl Into<String>) -> Self {
-        self.password = Some(password.into());
+        self.password = Some(PasswordSource::Inline(password.into()));
+        self
+    }
+
+    /// Read the password from a file at connect time, trimming surrounding
+    /// whitespace.
+    pub fn password_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.password = Some(PasswordSource::File(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Read the password from the environment variable `var` at connect
+    /// time, usually [`DEFAULT_PASSWORD_ENV`]. Connecting fails if it is
+    /// not set.
+    pub fn password_env(mut self, var: impl Into<String>) -> Self {
+        self.password = Some(PasswordSource::Env(var.into()));
         self
     }
 
@@ -124,7 +476,8 @@ impl KittyBuilder {
     ///
     /// Public key resolution order (when password is set):
     /// 1. Explicit key set via `.public_key()` method
-    /// 2. Query kitty-pubkey-db database (extracts PID from socket path)
+    /// 2. Query kitty-pubkey-db database (extracts PID from socket path),
+    ///    skipped if the helper isn't installed
     /// 3. KITTY_PUBLIC_KEY environment variable (set by kitty when launching subprocesses)
     ///
     /// When no password is set, no encryption is used.
@@ -135,19 +488,14 @@ impl KittyBuilder {
             ))
         })?;
 
-        let stream = timeout(self.timeout, UnixStream::connect(&socket_path))
-            .await
-            .map_err(|_| ConnectionError::TimeoutError(self.timeout))?
-            .map_err(|e| ConnectionError::ConnectionFailed(socket_path.clone(), e))?;
-
-        let encryptor = if self.password.is_some() {
-            let public_key = if let Some(pk) = self.public_key {
-                Some(pk)
-            } else if let Some(pid) = Self::extract_pid_from_socket(&socket_path) {
-                Self::query_public_key_database(pid).map_err(KittyError::Encryption)?
-            } else {
-                None
-            };
+        let stream = connect_socket(&socket_path, self.timeout).await?;
+
+        let password = self.password.map(PasswordSource::resolve).transpose()?;
+
+        let encryptor = if password.is_some() {
+            let public_key = Self::resolve_public_key(self.public_key, &socket_path, |pid| {
+                Self::query_public_key_database(PUBKEY_DB_PROGRAM, pid)
+            })?;
 
             Some(Encryptor::new_with_public_key(public_key.as_deref())?)
         } else {
@@ -157,13 +505,104 @@ impl KittyBuilder {
         Ok(Kitty {
             stream,
             timeout: self.timeout,
+            interactive_timeout: self.interactive_timeout,
+            poll_interval: self.poll_interval,
+            max_response_bytes: self.max_response_bytes,
             socket_path,
-            password: self.password,
+            password,
             encryptor,
+            on_reconnect: self.on_reconnect,
+            on_progress: self.on_progress,
         })
     }
 }
 
+/// Connect to the socket at `path`, reporting a missing socket as
+/// [`ConnectionError::SocketNotFound`] with the path, including one that
+/// disappears between the check and the connect.
+async fn connect_socket(path: &str, duration: Duration) -> Result<UnixStream, ConnectionError> {
+    if !Path::new(path).exists() {
+        return Err(ConnectionError::SocketNotFound(path.to_string()));
+    }
+
+    match timeout(duration, UnixStream::connect(path)).await {
+        Ok(Ok(stream)) => Ok(stream),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(ConnectionError::SocketNotFound(path.to_string()))
+        }
+        Ok(Err(e)) => Err(ConnectionError::ConnectionFailed(path.to_string(), e)),
+        Err(_) => Err(ConnectionError::TimeoutError(duration)),
+    }
+}
+
+/// Record the public key of the kitty instance this process runs in, like
+/// `kitty-pubkey-db add` does, so standalone clients can find it later.
+///
+/// Reads `KITTY_PID`, `KITTY_PUBLIC_KEY` and (optionally) `KITTY_WINDOW_ID`
+/// and appends to the database's default TSV file, returning its path.
+pub fn register_self_pubkey() -> Result<PathBuf, EncryptionError> {
+    let pid = std::env::var("KITTY_PID")
+        .ok()
+        .and_then(|pid| pid.parse().ok())
+        .ok_or_else(|| EncryptionError::PublicKeyDatabaseError("KITTY_PID not set".to_string()))?;
+    let pubkey =
+        std::env::var("KITTY_PUBLIC_KEY").map_err(|_| EncryptionError::MissingPublicKey)?;
+    let window_id = std::env::var("KITTY_WINDOW_ID")
+        .ok()
+        .and_then(|id| id.parse().ok());
+
+    let db_path = pubkey_db_path();
+    if let Some(dir) = db_path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            EncryptionError::PublicKeyDatabaseError(format!("{}: {}", dir.display(), e))
+        })?;
+    }
+
+    append_pubkey_entry(&db_path, pid, window_id, &pubkey)?;
+    Ok(db_path)
+}
+
+/// `$XDG_STATE_HOME/kitty/pubkey.tsv`, where `kitty-pubkey-db` keeps its
+/// entries by default.
+fn pubkey_db_path() -> PathBuf {
+    let state_home = std::env::var("XDG_STATE_HOME").unwrap_or_else(|_| {
+        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+        format!("{}/.local/state", home)
+    });
+
+    PathBuf::from(state_home).join("kitty").join("pubkey.tsv")
+}
+
+/// Append one `pid<TAB>window_id<TAB>pubkey<TAB>timestamp` line. The line
+/// goes out in a single append-mode write, as with `kitty-pubkey-db`, so
+/// concurrent writers don't interleave.
+fn append_pubkey_entry(
+    db_path: &Path,
+    pid: u32,
+    window_id: Option<u32>,
+    pubkey: &str,
+) -> Result<(), EncryptionError> {
+    use std::io::Write;
+
+    let db_error = |e: std::io::Error| {
+        EncryptionError::PublicKeyDatabaseError(format!("{}: {}", db_path.display(), e))
+    };
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let window_id = window_id.map(|id| id.to_string()).unwrap_or_default();
+    let line = format!("{}\t{}\t{}\t{}\n", pid, window_id, pubkey, timestamp);
+
+    std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(db_path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(db_error)
+}
+
 impl Kitty {
     pub fn builder() -> KittyBuilder {
         KittyBuilder::new()
@@ -205,9 +644,19 @@ impl Kitty {
         Ok(message)
     }
 
-    async fn send(&mut self, message: &KittyMessage) -> Result<(), KittyError> {
+    /// The exact bytes [`execute`](Self::execute) would write for
+    /// `message`, including password and timestamp injection and
+    /// encryption, without touching the socket.
+    ///
+    /// Encrypted output differs between calls since every encryption uses
+    /// a fresh key pair and nonce.
+    pub fn dry_run(&self, message: &KittyMessage) -> Result<Vec<u8>, KittyError> {
         let encrypted_msg = self.encrypt_command(message.clone())?;
-        let data = encrypted_msg.encode()?;
+        Ok(encrypted_msg.encode()?)
+    }
+
+    async fn send(&mut self, message: &KittyMessage) -> Result<(), KittyError> {
+        let data = self.dry_run(message)?;
 
         timeout(self.timeout, self.stream.write_all(&data))
             .await
@@ -217,15 +666,48 @@ impl Kitty {
     }
 
     async fn receive(&mut self) -> Result<KittyResponse, KittyError> {
-        const SUFFIX: &[u8] = b"\x1b\\";
+        self.receive_within(Some(self.timeout)).await
+    }
+
+    /// The read timeout for the response to `message`; `None` waits forever.
+    fn read_timeout(&self, message: &KittyMessage) -> Option<Duration> {
+        if message.interactive {
+            self.interactive_timeout
+        } else {
+            Some(self.timeout)
+        }
+    }
 
+    async fn receive_within(
+        &mut self,
+        read_timeout: Option<Duration>,
+    ) -> Result<KittyResponse, KittyError> {
         let mut buffer = Vec::new();
 
         loop {
+            // Interim envelopes (progress, acks) may precede the result,
+            // possibly in the same read.
+            while let Some(len) = KittyResponse::frame_len(&buffer) {
+                let frame: Vec<u8> = buffer.drain(..len).collect();
+                let (kind, response) = KittyResponse::decode_kind(&frame)?;
+                if kind == ResponseKind::Final {
+                    return Ok(response);
+                }
+                if let Some(callback) = &self.on_progress {
+                    callback(kind, &response);
+                }
+            }
+            if !buffer.is_empty() && KittyResponse::is_complete(&buffer) {
+                break;
+            }
+
             let mut chunk = vec![0u8; 8192];
-            let n = timeout(self.timeout, self.stream.read(&mut chunk))
-                .await
-                .map_err(|_| ConnectionError::TimeoutError(self.timeout))??;
+            let n = match read_timeout {
+                Some(duration) => timeout(duration, self.stream.read(&mut chunk))
+                    .await
+                    .map_err(|_| ConnectionError::TimeoutError(duration))??,
+                None => self.stream.read(&mut chunk).await?,
+            };
 
             if n == 0 {
                 break;
@@ -233,8 +715,12 @@ impl Kitty {
 
             buffer.extend_from_slice(&chunk[..n]);
 
-            if buffer.ends_with(SUFFIX) {
-                break;
+            if buffer.len() > self.max_response_bytes {
+                return Err(ProtocolError::PayloadValidationError(format!(
+                    "response exceeds maximum size of {} bytes",
+                    self.max_response_bytes
+                ))
+                .into());
             }
         }
 
@@ -246,11 +732,112 @@ impl Kitty {
     }
 
     pub async fn execute(&mut self, message: &KittyMessage) -> Result<KittyResponse, KittyError> {
+        if message.only_at_prompt {
+            self.ensure_at_prompt(message).await?;
+        }
+
+        self.send(message).await?;
+        self.receive_within(self.read_timeout(message)).await
+    }
+
+    /// Build `command` and execute it, sparing the separate `build()?`
+    /// step. Accepts any builder in this crate; a failed build surfaces
+    /// as [`KittyError::Command`].
+    ///
+    /// ```no_run
+    /// # use kitty_rc::{Kitty, LsCommand};
+    /// # async fn example(kitty: &mut Kitty) -> Result<(), kitty_rc::KittyError> {
+    /// let response = kitty.execute_command(LsCommand::new()).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn execute_command<C>(&mut self, command: C) -> Result<KittyResponse, KittyError>
+    where
+        C: TryInto<KittyMessage, Error = CommandError>,
+    {
+        let message = command.try_into()?;
+        self.execute(&message).await
+    }
+
+    /// Send a hand-written command: `cmd` with `payload` as is, bypassing
+    /// the typed builders. Meant for debugging, e.g. trying commands or
+    /// fields this crate does not know yet. An empty `cmd` fails with
+    /// [`CommandError::InvalidCommand`] before anything is sent.
+    pub async fn execute_raw_json(
+        &mut self,
+        cmd: &str,
+        payload: serde_json::Value,
+    ) -> Result<KittyResponse, KittyError> {
+        if cmd.trim().is_empty() {
+            return Err(CommandError::InvalidCommand(cmd.to_string()).into());
+        }
+
+        let message = CommandBuilder::new(cmd).payload(payload).build();
+        self.execute(&message).await
+    }
+
+    /// Wrap this client in a [`SharedKitty`] so several tasks can use it,
+    /// e.g. with [`spawn_execute`](Self::spawn_execute).
+    pub fn into_shared(self) -> SharedKitty {
+        Arc::new(tokio::sync::Mutex::new(self))
+    }
+
+    /// Run `message` on a background task and return a handle to await its
+    /// response later, for fire-and-forget callers that still want the
+    /// result.
+    ///
+    /// Concurrency: the connection carries one request at a time, so the
+    /// task holds the client's lock for the whole round trip. Spawned
+    /// commands therefore run one after another, in the order they get the
+    /// lock (tokio's mutex is fair), never interleaved on the socket.
+    /// Callers using the client directly meanwhile wait for the lock too.
+    /// Must be called from within a tokio runtime.
+    pub fn spawn_execute(
+        kitty: &SharedKitty,
+        message: KittyMessage,
+    ) -> JoinHandle<Result<KittyResponse, KittyError>> {
+        let kitty = Arc::clone(kitty);
+        tokio::spawn(async move { kitty.lock().await.execute(&message).await })
+    }
+
+    /// Like [`execute`](Self::execute), but also reports how long the send
+    /// and the wait for the response took.
+    pub async fn execute_timed(
+        &mut self,
+        message: &KittyMessage,
+    ) -> Result<(KittyResponse, ExecTiming), KittyError> {
+        if message.only_at_prompt {
+            self.ensure_at_prompt(message).await?;
+        }
+
+        let start = Instant::now();
         self.send(message).await?;
-        self.receive().await
+        let send = start.elapsed();
+
+        let response = self.receive_within(self.read_timeout(message)).await?;
+        let total = start.elapsed();
+
+        Ok((
+            response,
+            ExecTiming {
+                send,
+                wait: total - send,
+                total,
+            },
+        ))
     }
 
     pub async fn send_all(&mut self, message: &KittyMessage) -> Result<(), KittyError> {
+        if message.only_at_prompt {
+            self.ensure_at_prompt(message).await?;
+        }
+
+        self.send_chunks(message).await
+    }
+
+    /// Send `message`, in chunks if it needs streaming, without the
+    /// [`ensure_at_prompt`](Self::ensure_at_prompt) guard.
+    async fn send_chunks(&mut self, message: &KittyMessage) -> Result<(), KittyError> {
         if message.needs_streaming() {
             for chunk in message.clone().into_chunks() {
                 let encrypted_chunk = self.encrypt_command(chunk)?;
@@ -264,12 +851,51 @@ impl Kitty {
         Ok(())
     }
 
+    /// Send `message`, split into chunks if it is too large for one, and
+    /// read the response. A chunked command answered with a transient
+    /// "stream closed" error is resent once, as a fresh stream, before the
+    /// error is returned. A message split into separate commands (see
+    /// [`KittyMessage::splits_into_commands`]) is sent one command at a
+    /// time, stopping at the first failed response, which is returned;
+    /// otherwise the last response is.
     pub async fn execute_all(
         &mut self,
         message: &KittyMessage,
     ) -> Result<KittyResponse, KittyError> {
+        if message.needs_streaming() && message.splits_into_commands() {
+            if message.only_at_prompt {
+                self.ensure_at_prompt(message).await?;
+            }
+
+            let mut last = None;
+            for command in message.clone().into_chunks() {
+                let encrypted = self.encrypt_command(command)?;
+                self.send(&encrypted).await?;
+                let response = self.receive_within(self.read_timeout(message)).await?;
+                if !response.ok {
+                    return Ok(response);
+                }
+                last = Some(response);
+            }
+            return last.ok_or_else(|| {
+                CommandError::ValidationError(format!("{}: nothing to send", message.cmd)).into()
+            });
+        }
+
         self.send_all(message).await?;
-        self.receive().await
+        let response = self.receive_within(self.read_timeout(message)).await?;
+
+        let stream_closed = !response.ok
+            && response
+                .error
+                .as_deref()
+                .is_some_and(|error| error.to_ascii_lowercase().contains(STREAM_CLOSED_ERROR));
+        if !message.needs_streaming() || !stream_closed {
+            return Ok(response);
+        }
+
+        self.send_chunks(message).await?;
+        self.receive_within(self.read_timeout(message)).await
     }
 
     pub async fn send_command<T: Into<KittyMessage>>(
@@ -279,81 +905,922 @@ impl Kitty {
         self.send_all(&command.into()).await
     }
 
-    pub async fn reconnect(&mut self) -> Result<(), KittyError> {
-        let _ = self.stream.shutdown().await;
+    /// Execute `message`, turning an `ok: false` reply into an error.
+    async fn execute_ok(
+        &mut self,
+        name: &str,
+        message: &KittyMessage,
+    ) -> Result<KittyResponse, KittyError> {
+        let response = self.execute(message).await?;
 
-        let new_stream = timeout(self.timeout, UnixStream::connect(&self.socket_path))
-            .await
-            .map_err(|_| ConnectionError::TimeoutError(self.timeout))?
-            .map_err(|e| ConnectionError::ConnectionFailed(self.socket_path.clone(), e))?;
+        if !response.ok {
+            return Err(CommandError::KittyError(
+                name.to_string(),
+                response.error.unwrap_or_default(),
+            )
+            .into());
+        }
 
-        self.stream = new_stream;
-        Ok(())
+        Ok(response)
     }
 
-    pub async fn close(&mut self) -> Result<(), KittyError> {
-        self.stream.shutdown().await.ok();
+    /// Guard for [`SendTextCommand::only_at_prompt`]: every window the
+    /// message targets must report `at_prompt`.
+    async fn ensure_at_prompt(&mut self, message: &KittyMessage) -> Result<(), KittyError> {
+        let spec = message
+            .payload
+            .as_ref()
+            .and_then(|payload| payload.get("match"))
+            .and_then(|spec| spec.as_str());
+
+        // Goes through send/receive rather than `ls` to keep `execute` from
+        // recursing into itself.
+        let mut ls = LsCommand::new();
+        if let Some(spec) = spec {
+            ls = ls.match_spec(spec);
+        }
+        self.send(&ls.build()?).await?;
+        let response = self.receive().await?;
+        if !response.ok {
+            return Err(CommandError::KittyError(
+                "ls".to_string(),
+                response.error.unwrap_or_default(),
+            )
+            .into());
+        }
+        let instances = LsCommand::parse_response(&response).map_err(ProtocolError::JsonError)?;
+
+        let windows: Vec<&WindowInfo> = match spec {
+            Some(_) => all_windows(&instances).collect(),
+            None => active_window(&instances).into_iter().collect(),
+        };
+
+        if windows.is_empty() {
+            return Err(CommandError::InvalidWindowMatch(
+                spec.unwrap_or("active window").to_string(),
+            )
+            .into());
+        }
+
+        if let Some(window) = windows.iter().find(|window| window.at_prompt != Some(true)) {
+            return Err(CommandError::ValidationError(format!(
+                "{}: window {} is not at a shell prompt",
+                message.cmd,
+                window
+                    .id
+                    .map_or_else(|| "?".to_string(), |id| id.to_string())
+            ))
+            .into());
+        }
+
         Ok(())
     }
-}
 
-impl Drop for Kitty {
-    fn drop(&mut self) {
-        let _ = self.stream.shutdown();
+    async fn ls(&mut self, command: LsCommand) -> Result<Vec<OsInstance>, KittyError> {
+        let response = self.execute_ok("ls", &command.build()?).await?;
+
+        LsCommand::parse_response(&response).map_err(|e| ProtocolError::JsonError(e).into())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// The id of the globally focused window, or `None` when no kitty
+    /// window has focus. kitty filters the `ls` tree down to the focused
+    /// window, and only its id and focus flag are parsed.
+    pub async fn focused_window_id(&mut self) -> Result<Option<WindowId>, KittyError> {
+        let message = LsCommand::new().match_spec("state:focused").build()?;
+        let response = self.execute_ok("ls", &message).await?;
 
-    #[test]
-    fn test_builder_creation() {
-        let builder = KittyBuilder::new()
-            .socket_path("/tmp/test.sock")
-            .timeout(Duration::from_secs(5));
+        LsCommand::parse_focused_id(&response).map_err(|e| ProtocolError::JsonError(e).into())
+    }
 
-        assert_eq!(builder.socket_path, Some("/tmp/test.sock".to_string()));
-        assert_eq!(builder.timeout, Duration::from_secs(5));
+    /// Wait for the process launched in the window matching `match_spec`
+    /// to exit, polling `ls` every [`KittyBuilder::poll_interval`].
+    ///
+    /// The launched process is the window's `pid`, i.e. the command given
+    /// to `launch` or the shell. It counts as exited once it drops out of
+    /// the window's foreground processes, or when the window closes. Fails
+    /// with [`CommandError::InvalidWindowMatch`] if nothing matches at the
+    /// start.
+    pub async fn wait_for_exit(
+        &mut self,
+        match_spec: impl Into<MatchSpec>,
+        timeout: Duration,
+    ) -> Result<ExitOutcome, KittyError> {
+        self.wait_for_exit_cancellable(match_spec, timeout, &CancellationToken::new())
+            .await
     }
 
-    #[test]
-    fn test_builder_with_password() {
-        let builder = KittyBuilder::new().password("test-password");
+    /// [`wait_for_exit`](Self::wait_for_exit), returning
+    /// [`ExitOutcome::Cancelled`] once `cancel` fires. Cancellation is only
+    /// observed between polls, so a request already sent to kitty still
+    /// gets its response read and the connection stays usable.
+    pub async fn wait_for_exit_cancellable(
+        &mut self,
+        match_spec: impl Into<MatchSpec>,
+        timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> Result<ExitOutcome, KittyError> {
+        let match_spec: String = match_spec.into().into();
+        let deadline = Instant::now() + timeout;
+
+        let window = self
+            .ls_filtered(match_spec.clone())
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(CommandError::InvalidWindowMatch(match_spec))?;
+        let (Some(window_id), Some(pid)) = (window.id, window.pid) else {
+            return Err(CommandError::ValidationError(
+                "wait_for_exit: kitty reported no id or pid for the window".to_string(),
+            )
+            .into());
+        };
+
+        let mut window = Some(window);
+        loop {
+            let Some(current) = window else {
+                return Ok(ExitOutcome::WindowClosed);
+            };
+            if !current
+                .foreground_processes
+                .iter()
+                .any(|process| process.pid == Some(pid))
+            {
+                return Ok(ExitOutcome::Exited);
+            }
+
+            if Instant::now() + self.poll_interval > deadline {
+                return Ok(ExitOutcome::TimedOut);
+            }
+            tokio::select! {
+                _ = cancel.cancelled() => return Ok(ExitOutcome::Cancelled),
+                _ = tokio::time::sleep(self.poll_interval) => {}
+            }
 
-        assert_eq!(builder.password, Some("test-password".to_string()));
+            window = self
+                .ls_filtered(format!("id:{}", window_id))
+                .await?
+                .into_iter()
+                .next();
+        }
     }
 
-    #[test]
-    fn test_builder_with_public_key() {
-        let builder = KittyBuilder::new().public_key("1:abc123");
+    /// Look up the `n`th most recently active window (`0` is the active one).
+    pub async fn recent_window(&mut self, n: u32) -> Result<Option<WindowInfo>, KittyError> {
+        Ok(self
+            .ls_filtered(MatchSpec::recent(n))
+            .await?
+            .into_iter()
+            .next())
+    }
 
-        assert_eq!(builder.public_key, Some("1:abc123".to_string()));
+    /// Whether the window matching `spec` is sitting at a shell prompt, as
+    /// tracked by kitty's shell integration.
+    ///
+    /// Fails with [`CommandError::InvalidWindowMatch`] when nothing matches
+    /// and with [`CommandError::ValidationError`] when kitty reports no
+    /// prompt state for the window, i.e. shell integration is unavailable.
+    pub async fn is_at_prompt(&mut self, spec: impl Into<MatchSpec>) -> Result<bool, KittyError> {
+        let spec: String = spec.into().into();
+        let window = self
+            .ls_filtered(spec.clone())
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(CommandError::InvalidWindowMatch(spec))?;
+
+        window.at_prompt.ok_or_else(|| {
+            CommandError::ValidationError(format!(
+                "window {} reports no prompt state; is shell integration enabled?",
+                window
+                    .id
+                    .map_or_else(|| "?".to_string(), |id| id.to_string())
+            ))
+            .into()
+        })
     }
 
-    #[test]
-    fn test_builder_from_pid() {
-        let builder = KittyBuilder::new().from_pid(12345);
+    /// The user variables set on the window matching `spec`, e.g. with
+    /// [`SetUserVarsCommand`], sorted by name.
+    /// Fails with [`CommandError::InvalidWindowMatch`] when nothing matches.
+    pub async fn user_vars(
+        &mut self,
+        spec: impl Into<MatchSpec>,
+    ) -> Result<BTreeMap<String, String>, KittyError> {
+        let spec: String = spec.into().into();
+        let window = self
+            .ls_filtered(spec.clone())
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(CommandError::InvalidWindowMatch(spec))?;
 
-        assert!(builder.socket_path.is_some());
-        assert!(builder.socket_path.as_ref().unwrap().ends_with("kitty-12345.sock"));
+        Ok(window.user_vars.into_iter().collect())
     }
 
-    #[test]
-    fn test_extract_pid_from_socket_standard() {
-        let pid = KittyBuilder::extract_pid_from_socket("/tmp/kitty-12345.sock");
-        assert_eq!(pid, Some(12345));
+    /// Set user variable `key` to `value` on every window, see
+    /// [`SetUserVarsCommand::all`].
+    pub async fn set_user_var_everywhere(
+        &mut self,
+        key: impl AsRef<str>,
+        value: impl AsRef<str>,
+    ) -> Result<(), KittyError> {
+        let message = SetUserVarsCommand::new(vec![format!("{}={}", key.as_ref(), value.as_ref())])
+            .all(true)
+            .build()?;
+        self.execute_ok("set-user-vars", &message).await?;
+        Ok(())
     }
 
-    #[test]
-    fn test_extract_pid_from_socket_xdg_runtime_dir() {
-        let pid = KittyBuilder::extract_pid_from_socket(
-            "/run/user/1000/kitty-67890.sock",
-        );
-        assert_eq!(pid, Some(67890));
+    /// List the windows matching `spec`, letting kitty do the filtering,
+    /// flattened out of their OS window and tab.
+    pub async fn ls_filtered(
+        &mut self,
+        spec: impl Into<MatchSpec>,
+    ) -> Result<Vec<WindowInfo>, KittyError> {
+        let instances = self.ls(LsCommand::new().match_spec(spec)).await?;
+
+        Ok(instances
+            .into_iter()
+            .flat_map(|instance| instance.tabs)
+            .flat_map(|tab| tab.windows)
+            .collect())
     }
 
-    #[test]
+    /// Turn this connection into a [`Sink`] that types each item into the
+    /// windows matching `spec`, as `send-text` with `no_response` so writes
+    /// don't wait for a round trip.
+    ///
+    /// Flow control: items go through a channel of
+    /// [`TEXT_SINK_CAPACITY`] entries drained by a background task, so
+    /// `send` only waits once that many writes are queued. Since kitty
+    /// sends no reply, a rejected command (e.g. nothing matched) is not
+    /// reported. If a write fails the task reconnects and retries the item
+    /// once, dropping it if that fails too. Closing or dropping the sink
+    /// ends the task and the connection.
+    pub fn text_sink(
+        mut self,
+        spec: impl Into<MatchSpec>,
+    ) -> impl Sink<String, Error = PollSendError<String>> {
+        let spec: String = spec.into().into();
+        let (tx, mut rx) = mpsc::channel::<String>(TEXT_SINK_CAPACITY);
+
+        tokio::spawn(async move {
+            while let Some(text) = rx.recv().await {
+                let message = match SendTextCommand::new(format!("text:{}", text))
+                    .match_spec(spec.clone())
+                    .build()
+                {
+                    Ok(message) => message.no_response(true),
+                    Err(_) => continue,
+                };
+
+                if self.send_all(&message).await.is_err() && self.reconnect().await.is_ok() {
+                    let _ = self.send_all(&message).await;
+                }
+            }
+        });
+
+        PollSender::new(tx)
+    }
+
+    /// Capture the text of the window matching `spec` with `get-text` and
+    /// write it to `path`, creating parent directories as needed. Returns
+    /// the number of bytes written.
+    pub async fn capture_to_file(
+        &mut self,
+        spec: impl Into<MatchSpec>,
+        path: impl AsRef<Path>,
+        opts: CaptureOptions,
+    ) -> Result<usize, KittyError> {
+        let mut command = GetTextCommand::new().match_spec(spec).ansi(opts.ansi);
+        if let Some(extent) = opts.extent {
+            command = command.extent(extent);
+        }
+
+        let response = self.execute_ok("get-text", &command.build()?).await?;
+        let text = GetTextCommand::parse_response(&response)?;
+
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, &text).await?;
+
+        Ok(text.len())
+    }
+
+    /// The text kitty feeds its scrollback pager for the window matching
+    /// `spec`: scrollback plus screen (`extent=all`) with ANSI formatting
+    /// and line-wrap markers, ready to pipe into an external pager such as
+    /// `less -R`.
+    ///
+    /// [`ShowScrollbackAction`](crate::ShowScrollbackAction) opens the same
+    /// content in kitty's own `scrollback_pager`, inside kitty; this hands
+    /// it to the caller instead.
+    pub async fn scrollback_pager_input(
+        &mut self,
+        spec: impl Into<MatchSpec>,
+    ) -> Result<String, KittyError> {
+        let message = GetTextCommand::new()
+            .match_spec(spec)
+            .extent("all")
+            .ansi(true)
+            .wrap_markers(true)
+            .build()?;
+        let response = self.execute_ok("get-text", &message).await?;
+
+        Ok(GetTextCommand::parse_response(&response)?)
+    }
+
+    /// Set the title of each window matched by the `(match, title)` pairs,
+    /// reusing this connection for every `set-window-title`.
+    ///
+    /// Returns one result per pair, in order. A failing item does not stop
+    /// the rest.
+    pub async fn set_titles(
+        &mut self,
+        titles: impl IntoIterator<Item = (String, String)>,
+    ) -> Vec<Result<(), KittyError>> {
+        let mut results = Vec::new();
+
+        for (spec, title) in titles {
+            let result = match SetWindowTitleCommand::new(title).match_spec(spec).build() {
+                Ok(message) => self
+                    .execute_ok("set-window-title", &message)
+                    .await
+                    .map(|_| ()),
+                Err(e) => Err(e.into()),
+            };
+            results.push(result);
+        }
+
+        results
+    }
+
+    /// Check that remote control is usable by running a trivial `ls`.
+    ///
+    /// A reply that does not arrive within the timeout is reported as
+    /// [`RcStatus::AskPending`], since kitty holds the reply while it asks
+    /// the user to allow the request. Other transport errors are returned.
+    pub async fn check_remote_control(&mut self) -> Result<RcStatus, KittyError> {
+        match self.execute(&LsCommand::new().build()?).await {
+            Ok(response) => Ok(RcStatus::from_response(&response)),
+            Err(KittyError::Connection(ConnectionError::TimeoutError(_))) => {
+                Ok(RcStatus::AskPending)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Go back to the previously active tab, like `goto_tab -1`.
+    ///
+    /// This follows kitty's activation history, unlike the `previous_tab`
+    /// action which moves to the tab on the left.
+    pub async fn previous_tab(&mut self) -> Result<(), KittyError> {
+        self.execute_ok("goto_tab", &GotoTabAction::new(-1).build()?)
+            .await?;
+        Ok(())
+    }
+
+    /// Switch to the most recently used tab other than the current one, so
+    /// calling it twice toggles between two tabs. Returns `false` when
+    /// there is no other tab to go back to.
+    ///
+    /// This focuses the `recent:1` tab match rather than running the
+    /// `goto_tab -1` action of [`previous_tab`](Self::previous_tab), so it
+    /// still works where actions are not allowed over remote control.
+    /// kitty keeps the tab history internally; `ls` does not report it.
+    pub async fn alternate_tab(&mut self) -> Result<bool, KittyError> {
+        let message = FocusTabCommand::new()
+            .match_spec(MatchSpec::recent(1))
+            .build()?;
+        let response = self.execute(&message).await?;
+
+        match response.error {
+            _ if response.ok => Ok(true),
+            Some(error) if error.starts_with("No matching tabs") => Ok(false),
+            error => Err(CommandError::KittyError(
+                "focus-tab".to_string(),
+                error.unwrap_or_default(),
+            )
+            .into()),
+        }
+    }
+
+    /// Go back to the previously active window in the current tab, like
+    /// `nth_window -1`. See [`TabInfo::active_window_history`] for the
+    /// history itself.
+    ///
+    /// [`TabInfo::active_window_history`]: crate::commands::window::TabInfo::active_window_history
+    pub async fn previous_window(&mut self) -> Result<(), KittyError> {
+        self.execute_ok("nth_window", &NthWindowAction::new(-1).build()?)
+            .await?;
+        Ok(())
+    }
+
+    /// All OS windows with their tabs and windows, in kitty's order.
+    pub async fn list_os_windows(&mut self) -> Result<Vec<OsInstance>, KittyError> {
+        self.ls(LsCommand::new()).await
+    }
+
+    /// Focus the OS window holding the window matched by `spec`. kitty
+    /// has no OS window match syntax, so this focuses a window inside it.
+    pub async fn focus_os_window(&mut self, spec: impl Into<MatchSpec>) -> Result<(), KittyError> {
+        let message = FocusWindowCommand::new().match_spec(spec).build()?;
+        self.execute_ok("focus-window", &message).await?;
+        Ok(())
+    }
+
+    /// Focus the window whose title matches the regex `pattern`, see
+    /// [`MatchSpec::title`]. Returns `false` when no window matched,
+    /// rather than failing.
+    pub async fn focus_by_title(&mut self, pattern: &str) -> Result<bool, KittyError> {
+        let message = FocusWindowCommand::new()
+            .match_spec(MatchSpec::title(pattern))
+            .build()?;
+        let response = self.execute(&message).await?;
+
+        match response.error {
+            _ if response.ok => Ok(true),
+            Some(error) if error.starts_with("No matching windows") => Ok(false),
+            error => Err(CommandError::KittyError(
+                "focus-window".to_string(),
+                error.unwrap_or_default(),
+            )
+            .into()),
+        }
+    }
+
+    /// Focus the OS window after the focused one, wrapping around.
+    pub async fn next_os_window(&mut self) -> Result<(), KittyError> {
+        self.step_os_window(1).await
+    }
+
+    /// Focus the OS window before the focused one, wrapping around.
+    pub async fn prev_os_window(&mut self) -> Result<(), KittyError> {
+        self.step_os_window(-1).await
+    }
+
+    /// kitty only has an absolute `nth_os_window` action, so work out the
+    /// position relative to the focused OS window from `ls`.
+    async fn step_os_window(&mut self, step: isize) -> Result<(), KittyError> {
+        let instances = self.list_os_windows().await?;
+        if instances.is_empty() {
+            return Ok(());
+        }
+
+        let current = instances
+            .iter()
+            .position(|instance| instance.is_focused == Some(true))
+            .unwrap_or(0);
+        let target = (current as isize + step).rem_euclid(instances.len() as isize);
+
+        let message = ActionCommand::new("nth_os_window")
+            .arg((target + 1).to_string())
+            .build()?;
+        self.execute_ok("nth_os_window", &message).await?;
+        Ok(())
+    }
+
+    /// The first tab matching `tab_match`, or
+    /// [`CommandError::InvalidTabMatch`] if there is none.
+    async fn resolve_tab(&mut self, tab_match: &str) -> Result<TabInfo, KittyError> {
+        self.ls(LsCommand::new().match_tab(tab_match))
+            .await?
+            .into_iter()
+            .flat_map(|instance| instance.tabs)
+            .next()
+            .ok_or_else(|| CommandError::InvalidTabMatch(tab_match.to_string()).into())
+    }
+
+    /// Give all windows in the tab matching `tab_match` equal sizes.
+    ///
+    /// This runs kitty's `reset_window_sizes` action, which resets the
+    /// layout of the tab holding the window it is applied to, on a window
+    /// of the matched tab. The tab is looked up with `ls` first so a
+    /// missing tab is an error rather than a silent no-op.
+    pub async fn balance_windows(
+        &mut self,
+        tab_match: impl Into<String>,
+    ) -> Result<(), KittyError> {
+        let tab_match = tab_match.into();
+        let tab = self.resolve_tab(&tab_match).await?;
+
+        let window_id = tab
+            .windows
+            .iter()
+            .find_map(|window| window.id)
+            .ok_or(CommandError::InvalidTabMatch(tab_match))?;
+
+        let message = ResetWindowSizesAction::new().window_id(window_id).build()?;
+        self.execute_ok("reset_window_sizes", &message).await?;
+        Ok(())
+    }
+
+    /// Switch the tab matching `tab_match` to the layout after its current
+    /// one in `layouts`, wrapping around, and return the layout chosen.
+    ///
+    /// Unlike the `next_layout` action this only visits the given layouts,
+    /// e.g. `["tall", "stack"]`. A tab whose current layout is not in the
+    /// list moves to the first one.
+    pub async fn cycle_layout(
+        &mut self,
+        tab_match: impl Into<String>,
+        layouts: &[impl AsRef<str>],
+    ) -> Result<String, KittyError> {
+        if layouts.is_empty() {
+            return Err(CommandError::MissingParameter(
+                "layouts".to_string(),
+                "goto-layout".to_string(),
+            )
+            .into());
+        }
+
+        let tab_match = tab_match.into();
+        let tab = self.resolve_tab(&tab_match).await?;
+
+        let current = layouts
+            .iter()
+            .position(|layout| Some(layout.as_ref()) == tab.layout.as_deref());
+        let next = layouts[current.map_or(0, |i| (i + 1) % layouts.len())].as_ref();
+
+        let message = GotoLayoutCommand::new(next).match_spec(tab_match).build()?;
+        self.execute_ok("goto-layout", &message).await?;
+
+        Ok(next.to_string())
+    }
+
+    /// Put the windows matching `spec` in the wanted marker state: with
+    /// `enabled` the marker described by `marker_spec` (e.g.
+    /// `"text 1 ERROR"`) is created, replacing any existing one, otherwise
+    /// the marker is removed and `marker_spec` is ignored.
+    ///
+    /// kitty does not report markers in `ls`, so there is no way to read
+    /// the current state back; both paths are safe to repeat.
+    pub async fn set_marker(
+        &mut self,
+        spec: impl Into<MatchSpec>,
+        marker_spec: impl Into<String>,
+        enabled: bool,
+    ) -> Result<(), KittyError> {
+        if enabled {
+            let message = CreateMarkerCommand::new()
+                .match_spec(spec)
+                .marker_spec(marker_spec)
+                .build()?;
+            self.execute_ok("create-marker", &message).await?;
+        } else {
+            let message = RemoveMarkerCommand::new().match_spec(spec).build()?;
+            self.execute_ok("remove-marker", &message).await?;
+        }
+        Ok(())
+    }
+
+    /// Move the tabs matching `tab_match` into the OS window holding the
+    /// windows matching `os_window_match`, e.g. `"state:focused"`.
+    ///
+    /// kitty's `detach-tab` targets a tab rather than an OS window, so both
+    /// sides are resolved with `ls` first: the tab match must find a tab,
+    /// and the windows matching `os_window_match` must all live in one OS
+    /// window. The move is aimed at a tab of that OS window. With a known
+    /// id, use [`move_tab_to_os_window_id`](Self::move_tab_to_os_window_id).
+    pub async fn move_tab_to_os_window(
+        &mut self,
+        tab_match: impl Into<String>,
+        os_window_match: impl Into<MatchSpec>,
+    ) -> Result<(), KittyError> {
+        let tab_match = tab_match.into();
+        let os_window_match: String = os_window_match.into().into();
+        self.resolve_tab(&tab_match).await?;
+
+        let instances = self
+            .ls(LsCommand::new().match_spec(os_window_match.clone()))
+            .await?;
+        let mut targets = all_windows_with_ids(&instances)
+            .filter_map(|(os_window_id, tab_id, _)| Some((os_window_id?, tab_id?)));
+        let (os_window_id, target_tab) = targets
+            .next()
+            .ok_or_else(|| CommandError::InvalidWindowMatch(os_window_match.clone()))?;
+        if targets.any(|(other, _)| other != os_window_id) {
+            return Err(CommandError::ValidationError(format!(
+                "{} matches windows in more than one OS window",
+                os_window_match
+            ))
+            .into());
+        }
+
+        self.detach_tab_to(tab_match, target_tab).await
+    }
+
+    /// Move the tabs matching `tab_match` into the OS window with id
+    /// `os_window_id`, like [`move_tab_to_os_window`](Self::move_tab_to_os_window).
+    pub async fn move_tab_to_os_window_id(
+        &mut self,
+        tab_match: impl Into<String>,
+        os_window_id: impl Into<OsWindowId>,
+    ) -> Result<(), KittyError> {
+        let tab_match = tab_match.into();
+        let os_window_id = os_window_id.into();
+        self.resolve_tab(&tab_match).await?;
+
+        let instances = self.ls(LsCommand::new()).await?;
+        let target_tab = instances
+            .iter()
+            .find(|instance| instance.id == Some(os_window_id))
+            .and_then(|instance| instance.tabs.iter().find_map(|tab| tab.id))
+            .ok_or_else(|| {
+                CommandError::ValidationError(format!("no OS window with id {}", os_window_id))
+            })?;
+
+        self.detach_tab_to(tab_match, target_tab).await
+    }
+
+    /// Detach the tabs matching `tab_match` next to the tab `target_tab`.
+    async fn detach_tab_to(
+        &mut self,
+        tab_match: String,
+        target_tab: TabId,
+    ) -> Result<(), KittyError> {
+        let message = DetachTabCommand::new()
+            .match_spec(tab_match)
+            .target_tab(format!("id:{}", target_tab))
+            .build()?;
+        self.execute_ok("detach-tab", &message).await?;
+
+        Ok(())
+    }
+
+    /// Move the window matching `match_spec` into a new OS window of its
+    /// own and return that OS window's id.
+    ///
+    /// kitty's `detach-window` creates a new OS window when no `target_tab`
+    /// is given. The window is resolved with `ls` first, detached by id so
+    /// only that one window moves, and its new OS window is looked up with
+    /// a second `ls`.
+    pub async fn pop_out(
+        &mut self,
+        match_spec: impl Into<MatchSpec>,
+    ) -> Result<OsWindowId, KittyError> {
+        let match_spec: String = match_spec.into().into();
+        let window_id = self
+            .ls_filtered(match_spec.clone())
+            .await?
+            .into_iter()
+            .find_map(|window| window.id)
+            .ok_or(CommandError::InvalidWindowMatch(match_spec))?;
+
+        let message = DetachWindowCommand::new()
+            .match_spec(format!("id:{}", window_id))
+            .build()?;
+        self.execute_ok("detach-window", &message).await?;
+
+        let instances = self.ls(LsCommand::new()).await?;
+        all_windows_with_ids(&instances)
+            .find(|(_, _, window)| window.id == Some(window_id))
+            .and_then(|(os_id, _, _)| os_id)
+            .ok_or_else(|| {
+                CommandError::ValidationError(format!(
+                    "window {} not found after detaching it",
+                    window_id
+                ))
+                .into()
+            })
+    }
+
+    /// Query the configured font family and size.
+    ///
+    /// kitty has no dedicated rc command for this, so it runs the
+    /// `debug_config` action and parses the text it returns. Versions of
+    /// kitty that only show that output in a window, without returning it,
+    /// yield a [`ProtocolError::MissingField`] error.
+    pub async fn font_info(&mut self) -> Result<FontInfo, KittyError> {
+        let response = self
+            .execute_ok("debug_config", &DebugConfigCommand::new().build()?)
+            .await?;
+
+        DebugConfigCommand::parse_response(&response)
+            .map(|summary| summary.font)
+            .ok_or_else(|| ProtocolError::MissingField("data".to_string()).into())
+    }
+
+    /// Borrow the underlying socket, e.g. to inspect socket options.
+    ///
+    /// This is an advanced escape hatch and not covered by semver
+    /// guarantees. The client keeps no buffered framing state between
+    /// calls, so it is safe to use between commands.
+    pub fn get_ref(&self) -> &UnixStream {
+        &self.stream
+    }
+
+    /// Mutably borrow the underlying socket.
+    ///
+    /// Like [`get_ref`](Self::get_ref) this is advanced and unstable.
+    /// Reading or writing on the stream yourself while a response is
+    /// pending will desynchronize the next [`execute`](Self::execute).
+    pub fn get_mut(&mut self) -> &mut UnixStream {
+        &mut self.stream
+    }
+
+    pub async fn reconnect(&mut self) -> Result<(), KittyError> {
+        let _ = self.stream.shutdown().await;
+
+        let new_stream = connect_socket(&self.socket_path, self.timeout).await?;
+
+        self.stream = new_stream;
+
+        if let Some(callback) = &self.on_reconnect {
+            callback();
+        }
+        Ok(())
+    }
+
+    pub async fn close(&mut self) -> Result<(), KittyError> {
+        self.stream.shutdown().await.ok();
+        Ok(())
+    }
+}
+
+impl Drop for Kitty {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::style::SetBackgroundImageCommand;
+    use crate::commands::window::SelectWindowCommand;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::UnixListener;
+
+    static MOCK_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    /// A scripted reply from the mock kitty for one received message.
+    enum Reply {
+        Json(String),
+        Delayed(Duration, String),
+        /// Raw bytes written piece by piece, with a pause between pieces.
+        Pieces(Vec<Vec<u8>>),
+    }
+
+    fn mock_socket_path() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "kitty-rc-test-{}-{}.sock",
+            std::process::id(),
+            MOCK_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    /// Serve one connection, answering each received message with the next
+    /// scripted reply. Returns every message received once the client hangs up.
+    fn spawn_mock(replies: Vec<Reply>) -> (PathBuf, JoinHandle<Vec<KittyMessage>>) {
+        let path = mock_socket_path();
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let cleanup = path.clone();
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut replies = replies.into_iter();
+            let mut received = Vec::new();
+            let mut buffer = Vec::new();
+
+            loop {
+                let mut chunk = [0u8; 8192];
+                let n = stream.read(&mut chunk).await.unwrap_or(0);
+                if n == 0 {
+                    break;
+                }
+                buffer.extend_from_slice(&chunk[..n]);
+
+                while let Some(end) = buffer.windows(2).position(|w| w == b"\x1b\\") {
+                    let frame: Vec<u8> = buffer.drain(..end + 2).collect();
+                    received.push(KittyMessage::decode(&frame).unwrap());
+
+                    let body = match replies.next() {
+                        Some(Reply::Json(body)) => body,
+                        Some(Reply::Delayed(delay, body)) => {
+                            tokio::time::sleep(delay).await;
+                            body
+                        }
+                        Some(Reply::Pieces(pieces)) => {
+                            for piece in pieces {
+                                stream.write_all(&piece).await.unwrap();
+                                stream.flush().await.unwrap();
+                                tokio::time::sleep(Duration::from_millis(5)).await;
+                            }
+                            continue;
+                        }
+                        None => continue,
+                    };
+                    let framed = format!("\x1bP@kitty-cmd{}\x1b\\", body);
+                    stream.write_all(framed.as_bytes()).await.unwrap();
+                }
+            }
+
+            let _ = std::fs::remove_file(&cleanup);
+            received
+        });
+
+        (path, handle)
+    }
+
+    /// A successful reply carrying `data` the way kitty sends `ls` output:
+    /// as a JSON-encoded string.
+    fn ls_reply(data: serde_json::Value) -> Reply {
+        Reply::Json(serde_json::json!({"ok": true, "data": data.to_string()}).to_string())
+    }
+
+    /// A valid `1:<base85>` public key for building encrypting clients.
+    fn test_public_key() -> String {
+        let secret = x25519_dalek::StaticSecret::from([7u8; 32]);
+        let public_key = x25519_dalek::PublicKey::from(&secret);
+        format!("1:{}", base85::encode(public_key.as_bytes()))
+    }
+
+    async fn connect_mock(path: &Path) -> Kitty {
+        Kitty::builder().socket_path(path).connect().await.unwrap()
+    }
+
+    #[test]
+    fn test_builder_creation() {
+        let builder = KittyBuilder::new()
+            .socket_path("/tmp/test.sock")
+            .timeout(Duration::from_secs(5));
+
+        assert_eq!(builder.socket_path, Some("/tmp/test.sock".to_string()));
+        assert_eq!(builder.timeout, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_builder_max_response_bytes() {
+        let builder = KittyBuilder::new();
+        assert_eq!(builder.max_response_bytes, DEFAULT_MAX_RESPONSE_BYTES);
+
+        let builder = builder.max_response_bytes(1024);
+        assert_eq!(builder.max_response_bytes, 1024);
+    }
+
+    #[test]
+    fn test_builder_with_password() {
+        let builder = KittyBuilder::new().password("test-password");
+
+        assert_eq!(
+            builder.password,
+            Some(PasswordSource::Inline("test-password".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_builder_password_sources_last_wins() {
+        let builder = KittyBuilder::new()
+            .password("inline")
+            .password_file("/tmp/kitty-rc-password")
+            .password_env(DEFAULT_PASSWORD_ENV);
+        assert_eq!(
+            builder.password,
+            Some(PasswordSource::Env(DEFAULT_PASSWORD_ENV.to_string()))
+        );
+
+        let builder = builder.password("inline");
+        assert_eq!(
+            builder.password,
+            Some(PasswordSource::Inline("inline".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_builder_with_public_key() {
+        let builder = KittyBuilder::new().public_key("1:abc123");
+
+        assert_eq!(builder.public_key, Some("1:abc123".to_string()));
+    }
+
+    #[test]
+    fn test_builder_from_pid() {
+        let builder = KittyBuilder::new().from_pid(12345);
+
+        assert!(builder.socket_path.is_some());
+        assert!(builder.socket_path.as_ref().unwrap().ends_with("kitty-12345.sock"));
+    }
+
+    #[test]
+    fn test_extract_pid_from_socket_standard() {
+        let pid = KittyBuilder::extract_pid_from_socket("/tmp/kitty-12345.sock");
+        assert_eq!(pid, Some(12345));
+    }
+
+    #[test]
+    fn test_extract_pid_from_socket_xdg_runtime_dir() {
+        let pid = KittyBuilder::extract_pid_from_socket(
+            "/run/user/1000/kitty-67890.sock",
+        );
+        assert_eq!(pid, Some(67890));
+    }
+
+    #[test]
     fn test_extract_pid_from_socket_invalid() {
         let pid = KittyBuilder::extract_pid_from_socket("/tmp/invalid.sock");
         assert_eq!(pid, None);
@@ -365,12 +1832,42 @@ mod tests {
         assert_eq!(pid, None);
     }
 
+    #[test]
+    fn test_extract_pid_from_socket_custom_name() {
+        assert_eq!(
+            KittyBuilder::extract_pid_from_socket("/tmp/mysocket-999.sock"),
+            Some(999)
+        );
+        assert_eq!(
+            KittyBuilder::extract_pid_from_socket("/tmp/my-kitty-42"),
+            Some(42)
+        );
+        assert_eq!(
+            KittyBuilder::extract_pid_from_socket("/tmp/kitty.sock"),
+            None
+        );
+        assert_eq!(KittyBuilder::extract_pid_from_socket("/tmp/-42.sock"), None);
+    }
+
     #[test]
     fn test_extract_pid_from_socket_invalid_pid() {
         let pid = KittyBuilder::extract_pid_from_socket("/tmp/kitty-abc.sock");
         assert_eq!(pid, None);
     }
 
+    #[tokio::test]
+    async fn test_connect_nonexistent_socket() {
+        let path = mock_socket_path();
+        let result = Kitty::builder().socket_path(&path).connect().await;
+
+        match result {
+            Err(KittyError::Connection(ConnectionError::SocketNotFound(missing))) => {
+                assert_eq!(missing, path.to_string_lossy());
+            }
+            other => panic!("expected SocketNotFound, got {:?}", other.err()),
+        }
+    }
+
     #[tokio::test]
     async fn test_builder_missing_socket() {
         let builder = KittyBuilder::new();
@@ -378,4 +1875,1367 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_execute_timed() {
+        let delay = Duration::from_millis(50);
+        let (path, server) = spawn_mock(vec![Reply::Delayed(delay, r#"{"ok":true}"#.into())]);
+
+        let mut kitty = connect_mock(&path).await;
+        let msg = KittyMessage::new("ls", vec![0, 43, 1]);
+        let (response, timing) = kitty.execute_timed(&msg).await.unwrap();
+        kitty.close().await.unwrap();
+
+        assert!(response.ok);
+        assert!(timing.wait >= delay);
+        assert_eq!(timing.total, timing.send + timing.wait);
+        assert_eq!(server.await.unwrap()[0].cmd, "ls");
+    }
+
+    #[tokio::test]
+    async fn test_recent_window() {
+        let (path, server) = spawn_mock(vec![Reply::Json(
+            r#"{"ok":true,"data":"[{\"id\":1,\"tabs\":[{\"id\":2,\"windows\":[{\"id\":7,\"title\":\"vim\"}]}]}]"}"#
+                .into(),
+        )]);
+
+        let mut kitty = connect_mock(&path).await;
+        let window = kitty.recent_window(1).await.unwrap().unwrap();
+        kitty.close().await.unwrap();
+
+        assert_eq!(window.id, Some(WindowId(7)));
+        assert_eq!(window.title.as_deref(), Some("vim"));
+
+        let received = server.await.unwrap();
+        assert_eq!(received[0].cmd, "ls");
+        assert_eq!(received[0].payload.as_ref().unwrap()["match"], "recent:1");
+    }
+
+    #[tokio::test]
+    async fn test_max_response_bytes_exceeded() {
+        let body = format!(r#"{{"ok":true,"data":"{}"}}"#, "x".repeat(64 * 1024));
+        let (path, _server) = spawn_mock(vec![Reply::Json(body)]);
+
+        let mut kitty = Kitty::builder()
+            .socket_path(&path)
+            .max_response_bytes(1024)
+            .connect()
+            .await
+            .unwrap();
+
+        let msg = KittyMessage::new("get-text", vec![0, 43, 1]);
+        let result = kitty.execute(&msg).await;
+        assert!(matches!(
+            result,
+            Err(KittyError::Protocol(ProtocolError::PayloadValidationError(
+                _
+            )))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stream_accessors() {
+        let (path, server) = spawn_mock(vec![Reply::Json(r#"{"ok":true}"#.into())]);
+        let mut kitty = connect_mock(&path).await;
+
+        assert!(kitty.get_ref().peer_addr().is_ok());
+        let _: &mut UnixStream = kitty.get_mut();
+
+        let msg = KittyMessage::new("ls", vec![0, 43, 1]);
+        assert!(kitty.execute(&msg).await.unwrap().ok);
+
+        kitty.close().await.unwrap();
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_move_tab_to_os_window() {
+        let (path, server) = spawn_mock(vec![
+            ls_reply(serde_json::json!([{"id": 1, "tabs": [{"id": 3}]}])),
+            ls_reply(serde_json::json!([
+                {"id": 1, "tabs": [{"id": 3}]},
+                {"id": 2, "tabs": [{"id": 5}, {"id": 6}]}
+            ])),
+            Reply::Json(r#"{"ok":true}"#.into()),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        kitty.move_tab_to_os_window_id("id:3", 2).await.unwrap();
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received.len(), 3);
+        assert_eq!(received[0].payload.as_ref().unwrap()["match_tab"], "id:3");
+        assert_eq!(received[2].cmd, "detach-tab");
+        let payload = received[2].payload.as_ref().unwrap();
+        assert_eq!(payload["match"], "id:3");
+        assert_eq!(payload["target_tab"], "id:5");
+    }
+
+    #[tokio::test]
+    async fn test_move_tab_to_missing_os_window() {
+        let (path, server) = spawn_mock(vec![
+            ls_reply(serde_json::json!([{"id": 1, "tabs": [{"id": 3}]}])),
+            ls_reply(serde_json::json!([{"id": 1, "tabs": [{"id": 3}]}])),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        let result = kitty.move_tab_to_os_window_id("id:3", 9).await;
+        kitty.close().await.unwrap();
+
+        assert!(matches!(
+            result,
+            Err(KittyError::Command(CommandError::ValidationError(_)))
+        ));
+        let received = server.await.unwrap();
+        assert!(received.iter().all(|msg| msg.cmd != "detach-tab"));
+    }
+
+    #[tokio::test]
+    async fn test_move_tab_to_os_window_match() {
+        let (path, server) = spawn_mock(vec![
+            ls_reply(serde_json::json!([{"id": 1, "tabs": [{"id": 3}]}])),
+            ls_reply(serde_json::json!([
+                {"id": 2, "tabs": [{"id": 6, "windows": [{"id": 11}]}]}
+            ])),
+            Reply::Json(r#"{"ok":true}"#.into()),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        kitty
+            .move_tab_to_os_window("id:3", MatchSpec::id(11))
+            .await
+            .unwrap();
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received[1].payload.as_ref().unwrap()["match"], "id:11");
+        let payload = received[2].payload.as_ref().unwrap();
+        assert_eq!(received[2].cmd, "detach-tab");
+        assert_eq!(payload["match"], "id:3");
+        assert_eq!(payload["target_tab"], "id:6");
+    }
+
+    #[tokio::test]
+    async fn test_move_tab_to_ambiguous_os_window_match() {
+        let (path, server) = spawn_mock(vec![
+            ls_reply(serde_json::json!([{"id": 1, "tabs": [{"id": 3}]}])),
+            ls_reply(serde_json::json!([
+                {"id": 1, "tabs": [{"id": 3, "windows": [{"id": 10}]}]},
+                {"id": 2, "tabs": [{"id": 6, "windows": [{"id": 11}]}]}
+            ])),
+            ls_reply(serde_json::json!([{"id": 1, "tabs": [{"id": 3}]}])),
+            ls_reply(serde_json::json!([])),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        let ambiguous = kitty.move_tab_to_os_window("id:3", "title:vim").await;
+        let missing = kitty.move_tab_to_os_window("id:3", "title:none").await;
+        kitty.close().await.unwrap();
+
+        assert!(matches!(
+            ambiguous,
+            Err(KittyError::Command(CommandError::ValidationError(_)))
+        ));
+        assert!(matches!(
+            missing,
+            Err(KittyError::Command(CommandError::InvalidWindowMatch(_)))
+        ));
+        let received = server.await.unwrap();
+        assert!(received.iter().all(|msg| msg.cmd != "detach-tab"));
+    }
+
+    async fn connect_polling(path: &Path) -> Kitty {
+        Kitty::builder()
+            .socket_path(path)
+            .poll_interval(Duration::from_millis(1))
+            .connect()
+            .await
+            .unwrap()
+    }
+
+    fn window_with_processes(pids: &[u64]) -> Reply {
+        let processes: Vec<_> = pids
+            .iter()
+            .map(|pid| serde_json::json!({"pid": pid}))
+            .collect();
+        ls_reply(serde_json::json!([{"tabs": [{"windows": [
+            {"id": 5, "pid": 100, "foreground_processes": processes}
+        ]}]}]))
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_exit() {
+        let (path, server) = spawn_mock(vec![
+            window_with_processes(&[100]),
+            window_with_processes(&[100]),
+            window_with_processes(&[]),
+        ]);
+
+        let mut kitty = connect_polling(&path).await;
+        let outcome = kitty
+            .wait_for_exit("title:build", Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(outcome, ExitOutcome::Exited);
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received.len(), 3);
+        assert_eq!(
+            received[0].payload.as_ref().unwrap()["match"],
+            "title:build"
+        );
+        assert_eq!(received[2].payload.as_ref().unwrap()["match"], "id:5");
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_exit_window_closed() {
+        let (path, server) = spawn_mock(vec![
+            window_with_processes(&[100, 101]),
+            ls_reply(serde_json::json!([])),
+        ]);
+
+        let mut kitty = connect_polling(&path).await;
+        let outcome = kitty.wait_for_exit("id:5", Duration::from_secs(5)).await;
+        kitty.close().await.unwrap();
+
+        assert_eq!(outcome.unwrap(), ExitOutcome::WindowClosed);
+        assert_eq!(server.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_exit_timeout() {
+        let (path, server) = spawn_mock(vec![window_with_processes(&[100])]);
+
+        let mut kitty = Kitty::builder()
+            .socket_path(&path)
+            .poll_interval(Duration::from_secs(60))
+            .connect()
+            .await
+            .unwrap();
+        let outcome = kitty.wait_for_exit("id:5", Duration::from_millis(10)).await;
+        kitty.close().await.unwrap();
+
+        assert_eq!(outcome.unwrap(), ExitOutcome::TimedOut);
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_exit_cancelled() {
+        let (path, server) = spawn_mock(vec![window_with_processes(&[100])]);
+
+        let mut kitty = Kitty::builder()
+            .socket_path(&path)
+            .poll_interval(Duration::from_secs(60))
+            .connect()
+            .await
+            .unwrap();
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            trigger.cancel();
+        });
+
+        let started = Instant::now();
+        let outcome = kitty
+            .wait_for_exit_cancellable("id:5", Duration::from_secs(600), &cancel)
+            .await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+        kitty.close().await.unwrap();
+
+        assert_eq!(outcome.unwrap(), ExitOutcome::Cancelled);
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_command_builder() {
+        let (path, server) = spawn_mock(vec![ls_reply(serde_json::json!([{"id": 1}]))]);
+
+        let mut kitty = connect_mock(&path).await;
+        let response = kitty.execute_command(LsCommand::new()).await.unwrap();
+        assert_eq!(
+            LsCommand::parse_response(&response).unwrap()[0].id,
+            Some(OsWindowId(1))
+        );
+
+        let result = kitty.execute_command(SendTextCommand::new("")).await;
+        assert!(matches!(
+            result,
+            Err(KittyError::Command(CommandError::MissingParameter(_, _)))
+        ));
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].cmd, "ls");
+    }
+
+    #[tokio::test]
+    async fn test_execute_all_resends_on_stream_closed() {
+        let closed = r#"{"ok":false,"error":"Stream closed, resend"}"#;
+        let silent = || Reply::Pieces(Vec::new());
+        // Two data chunks and the end chunk per attempt; only the last of
+        // each attempt is answered.
+        let (path, server) = spawn_mock(vec![
+            silent(),
+            silent(),
+            Reply::Json(closed.into()),
+            silent(),
+            silent(),
+            Reply::Json(r#"{"ok":true}"#.into()),
+            Reply::Json(closed.into()),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        let message = SetBackgroundImageCommand::new("x".repeat(5000))
+            .build()
+            .unwrap();
+        let response = kitty.execute_all(&message).await.unwrap();
+        assert!(response.ok);
+
+        // Small commands are not streamed and get the error as is.
+        let small = SetBackgroundImageCommand::new("eA==").build().unwrap();
+        let response = kitty.execute_all(&small).await.unwrap();
+        assert!(!response.ok);
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received.len(), 7);
+        assert_ne!(received[0].stream_id, received[3].stream_id);
+    }
+
+    #[tokio::test]
+    async fn test_execute_all_splits_send_text() {
+        let ok = || Reply::Json(r#"{"ok":true}"#.into());
+        let (path, server) = spawn_mock(vec![
+            ok(),
+            ok(),
+            ok(),
+            Reply::Json(r#"{"ok":false,"error":"No matching windows"}"#.into()),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        let message = SendTextCommand::new(format!("text:{}", "x".repeat(9000)))
+            .match_spec("id:3")
+            .build()
+            .unwrap();
+        assert!(kitty.execute_all(&message).await.unwrap().ok);
+
+        let message = SendTextCommand::new(format!("text:{}", "y".repeat(5000)))
+            .match_spec("id:4")
+            .build()
+            .unwrap();
+        let response = kitty.execute_all(&message).await.unwrap();
+        assert_eq!(response.error.as_deref(), Some("No matching windows"));
+        kitty.close().await.unwrap();
+
+        // The second message stopped after its first, failed command.
+        let received = server.await.unwrap();
+        assert_eq!(received.len(), 4);
+        for (msg, spec) in received.iter().zip(["id:3", "id:3", "id:3", "id:4"]) {
+            let payload = msg.payload.as_ref().unwrap();
+            assert_eq!(msg.cmd, "send-text");
+            assert_eq!(payload["match"], spec);
+            assert!(payload["data"].as_str().unwrap().starts_with("text:"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_raw_json() {
+        let (path, server) = spawn_mock(vec![Reply::Json(
+            r#"{"ok": true, "data": "new-command"}"#.into(),
+        )]);
+
+        let mut kitty = connect_mock(&path).await;
+        let response = kitty
+            .execute_raw_json("new-command", serde_json::json!({"flag": true}))
+            .await
+            .unwrap();
+        assert_eq!(response.data.unwrap(), "new-command");
+        assert!(matches!(
+            kitty.execute_raw_json(" ", serde_json::json!({})).await,
+            Err(KittyError::Command(CommandError::InvalidCommand(_)))
+        ));
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].cmd, "new-command");
+        assert_eq!(received[0].payload.as_ref().unwrap()["flag"], true);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_execute() {
+        let (path, server) = spawn_mock(vec![
+            Reply::Delayed(
+                Duration::from_millis(20),
+                r#"{"ok":true,"data":"first"}"#.into(),
+            ),
+            Reply::Json(r#"{"ok":true,"data":"second"}"#.into()),
+        ]);
+
+        let kitty = connect_mock(&path).await.into_shared();
+        let first = Kitty::spawn_execute(&kitty, LsCommand::new().build().unwrap());
+        // let the first task take the lock before queueing the second
+        tokio::task::yield_now().await;
+        let second = Kitty::spawn_execute(&kitty, LsCommand::new().build().unwrap());
+
+        let first = first.await.unwrap().unwrap();
+        let second = second.await.unwrap().unwrap();
+        assert_eq!(first.data, Some(serde_json::json!("first")));
+        assert_eq!(second.data, Some(serde_json::json!("second")));
+
+        kitty.lock().await.close().await.unwrap();
+        assert_eq!(server.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cycle_layout() {
+        let tab =
+            |layout: &str| ls_reply(serde_json::json!([{"tabs": [{"id": 3, "layout": layout}]}]));
+        let ok = || Reply::Json(r#"{"ok":true}"#.into());
+        let (path, server) = spawn_mock(vec![
+            tab("tall"),
+            ok(),
+            tab("stack"),
+            ok(),
+            tab("grid"),
+            ok(),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        let layouts = ["tall", "stack"];
+        assert_eq!(kitty.cycle_layout("id:3", &layouts).await.unwrap(), "stack");
+        assert_eq!(kitty.cycle_layout("id:3", &layouts).await.unwrap(), "tall");
+        assert_eq!(kitty.cycle_layout("id:3", &layouts).await.unwrap(), "tall");
+        assert!(matches!(
+            kitty.cycle_layout("id:3", &[] as &[&str]).await,
+            Err(KittyError::Command(CommandError::MissingParameter(_, _)))
+        ));
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received.len(), 6);
+        assert_eq!(received[0].payload.as_ref().unwrap()["match_tab"], "id:3");
+        assert_eq!(received[1].cmd, "goto-layout");
+        let payload = received[1].payload.as_ref().unwrap();
+        assert_eq!(payload["layout"], "stack");
+        assert_eq!(payload["match"], "id:3");
+        assert_eq!(received[3].payload.as_ref().unwrap()["layout"], "tall");
+    }
+
+    #[tokio::test]
+    async fn test_pop_out() {
+        let (path, server) = spawn_mock(vec![
+            ls_reply(serde_json::json!([
+                {"id": 1, "tabs": [{"id": 2, "windows": [{"id": 4}]}]}
+            ])),
+            Reply::Json(r#"{"ok":true}"#.into()),
+            ls_reply(serde_json::json!([
+                {"id": 1, "tabs": [{"id": 2, "windows": [{"id": 3}]}]},
+                {"id": 7, "tabs": [{"id": 8, "windows": [{"id": 4}]}]}
+            ])),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        assert_eq!(kitty.pop_out("title:build").await.unwrap(), OsWindowId(7));
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(
+            received[0].payload.as_ref().unwrap()["match"],
+            "title:build"
+        );
+        assert_eq!(received[1].cmd, "detach-window");
+        let payload = received[1].payload.as_ref().unwrap();
+        assert_eq!(payload["match"], "id:4");
+        // no target tab is what makes kitty open a new OS window
+        assert!(payload.get("target_tab").is_none());
+        assert_eq!(received[2].cmd, "ls");
+    }
+
+    #[tokio::test]
+    async fn test_pop_out_no_match() {
+        let (path, server) = spawn_mock(vec![ls_reply(serde_json::json!([]))]);
+
+        let mut kitty = connect_mock(&path).await;
+        let result = kitty.pop_out("id:9").await;
+        kitty.close().await.unwrap();
+
+        assert!(matches!(
+            result,
+            Err(KittyError::Command(CommandError::InvalidWindowMatch(_)))
+        ));
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ls_filtered() {
+        let (path, server) = spawn_mock(vec![ls_reply(serde_json::json!([
+            {"id": 1, "tabs": [{"id": 2, "windows": [{"id": 4, "title": "build"}]}]},
+            {"id": 5, "tabs": [
+                {"id": 6, "windows": [{"id": 8, "title": "build logs"}]},
+                {"id": 7, "windows": []}
+            ]}
+        ]))]);
+
+        let mut kitty = connect_mock(&path).await;
+        let windows = kitty.ls_filtered("title:build").await.unwrap();
+        kitty.close().await.unwrap();
+
+        let ids: Vec<_> = windows.iter().filter_map(|w| w.id).collect();
+        assert_eq!(ids, vec![WindowId(4), WindowId(8)]);
+
+        let received = server.await.unwrap();
+        assert_eq!(received[0].cmd, "ls");
+        assert_eq!(
+            received[0].payload.as_ref().unwrap()["match"],
+            "title:build"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_plain() {
+        let (path, server) = spawn_mock(vec![]);
+        let mut kitty = connect_mock(&path).await;
+
+        let msg = LsCommand::new().build().unwrap();
+        assert_eq!(kitty.dry_run(&msg).unwrap(), msg.encode().unwrap());
+
+        kitty.close().await.unwrap();
+        assert!(server.await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_debug_redacts_password() {
+        let builder = Kitty::builder().password("hunter2");
+        let debug = format!("{:?}", builder);
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains("***"));
+
+        let (path, server) = spawn_mock(vec![]);
+        let mut kitty = Kitty::builder()
+            .socket_path(&path)
+            .password("hunter2")
+            .public_key(test_public_key())
+            .connect()
+            .await
+            .unwrap();
+
+        let debug = format!("{:?}", kitty);
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains(r#"password: Some("***")"#));
+        assert!(debug.contains(r#"encryptor: Some("***")"#));
+
+        kitty.close().await.unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_encrypted() {
+        let (path, server) = spawn_mock(vec![]);
+        let mut kitty = Kitty::builder()
+            .socket_path(&path)
+            .password("hunter2")
+            .public_key(test_public_key())
+            .connect()
+            .await
+            .unwrap();
+
+        let msg = LsCommand::new().build().unwrap();
+        let bytes = kitty.dry_run(&msg).unwrap();
+        kitty.close().await.unwrap();
+
+        let envelope = KittyMessage::decode(&bytes).unwrap();
+        assert_eq!(envelope.cmd, msg.cmd);
+        assert_eq!(envelope.version, msg.version);
+
+        let payload = envelope.payload.unwrap();
+        let mut keys: Vec<_> = payload.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, ["encrypted", "iv", "pubkey", "tag", "version"]);
+        assert!(!String::from_utf8_lossy(&bytes).contains("hunter2"));
+
+        assert!(server.await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_password_env() {
+        let var = format!("KITTY_RC_TEST_PASSWORD_{}", std::process::id());
+        // SAFETY: the variable name is unique to this test.
+        unsafe { std::env::set_var(&var, "from-env") };
+
+        let (path, _server) = spawn_mock(vec![]);
+        let kitty = Kitty::builder()
+            .socket_path(&path)
+            .password_env(&var)
+            .public_key(test_public_key())
+            .connect()
+            .await
+            .unwrap();
+
+        assert_eq!(kitty.password.as_deref(), Some("from-env"));
+        unsafe { std::env::remove_var(&var) };
+    }
+
+    #[tokio::test]
+    async fn test_password_env_unset() {
+        let (path, _server) = spawn_mock(vec![]);
+        let result = Kitty::builder()
+            .socket_path(&path)
+            .password_env("KITTY_RC_TEST_PASSWORD_UNSET")
+            .connect()
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(KittyError::Encryption(
+                EncryptionError::PasswordUnavailable(_)
+            ))
+        ));
+    }
+
+    fn os_windows_fixture() -> serde_json::Value {
+        serde_json::json!([
+            {"id": 1, "is_focused": false, "tabs": [{"id": 1, "windows": [{"id": 1}]}]},
+            {"id": 2, "is_focused": true, "tabs": [{"id": 2, "windows": [{"id": 2}]}]},
+            {"id": 3, "is_focused": false, "tabs": [{"id": 3, "windows": [{"id": 3}]}]}
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_list_os_windows() {
+        let (path, _server) = spawn_mock(vec![ls_reply(os_windows_fixture())]);
+
+        let mut kitty = connect_mock(&path).await;
+        let instances = kitty.list_os_windows().await.unwrap();
+
+        let ids: Vec<_> = instances.iter().filter_map(|i| i.id).collect();
+        assert_eq!(ids, vec![OsWindowId(1), OsWindowId(2), OsWindowId(3)]);
+        assert_eq!(instances[1].is_focused, Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_focus_os_window() {
+        let (path, server) = spawn_mock(vec![Reply::Json(r#"{"ok":true}"#.into())]);
+
+        let mut kitty = connect_mock(&path).await;
+        kitty.focus_os_window("id:3").await.unwrap();
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received[0].cmd, "focus-window");
+        assert_eq!(received[0].payload.as_ref().unwrap()["match"], "id:3");
+    }
+
+    #[tokio::test]
+    async fn test_next_and_prev_os_window() {
+        let (path, server) = spawn_mock(vec![
+            ls_reply(os_windows_fixture()),
+            Reply::Json(r#"{"ok":true}"#.into()),
+            ls_reply(os_windows_fixture()),
+            Reply::Json(r#"{"ok":true}"#.into()),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        kitty.next_os_window().await.unwrap();
+        kitty.prev_os_window().await.unwrap();
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        let action_args = |msg: &KittyMessage| {
+            let payload = msg.payload.as_ref().unwrap();
+            assert_eq!(payload["action"], "nth_os_window");
+            payload["args"][0].as_str().unwrap().to_string()
+        };
+        assert_eq!(action_args(&received[1]), "3");
+        assert_eq!(action_args(&received[3]), "1");
+    }
+
+    #[tokio::test]
+    async fn test_set_titles() {
+        let (path, server) = spawn_mock(vec![
+            Reply::Json(r#"{"ok":true}"#.into()),
+            Reply::Json(r#"{"ok":false,"error":"No matching windows"}"#.into()),
+            Reply::Json(r#"{"ok":true}"#.into()),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        let results = kitty
+            .set_titles([
+                ("id:1".to_string(), "editor".to_string()),
+                ("id:9".to_string(), "missing".to_string()),
+                ("id:2".to_string(), String::new()),
+                ("id:3".to_string(), "logs".to_string()),
+            ])
+            .await;
+        kitty.close().await.unwrap();
+
+        assert_eq!(results.len(), 4);
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1],
+            Err(KittyError::Command(CommandError::KittyError(_, _)))
+        ));
+        assert!(matches!(
+            results[2],
+            Err(KittyError::Command(CommandError::MissingParameter(_, _)))
+        ));
+        assert!(results[3].is_ok());
+
+        let received = server.await.unwrap();
+        let sent: Vec<_> = received
+            .iter()
+            .map(|msg| {
+                assert_eq!(msg.cmd, "set-window-title");
+                let payload = msg.payload.as_ref().unwrap();
+                (payload["match"].clone(), payload["title"].clone())
+            })
+            .collect();
+        assert_eq!(
+            sent,
+            vec![
+                (serde_json::json!("id:1"), serde_json::json!("editor")),
+                (serde_json::json!("id:9"), serde_json::json!("missing")),
+                (serde_json::json!("id:3"), serde_json::json!("logs")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rc_status_from_response() {
+        let response = |ok, error: Option<&str>| KittyResponse {
+            ok,
+            data: None,
+            error: error.map(str::to_string),
+        };
+
+        assert_eq!(
+            RcStatus::from_response(&response(true, None)),
+            RcStatus::Enabled
+        );
+        assert_eq!(
+            RcStatus::from_response(&response(
+                false,
+                Some("This instance requires a password for remote control")
+            )),
+            RcStatus::PasswordRequired
+        );
+        assert_eq!(
+            RcStatus::from_response(&response(
+                false,
+                Some("Waiting for the user to confirm the request")
+            )),
+            RcStatus::AskPending
+        );
+        assert_eq!(
+            RcStatus::from_response(&response(false, Some("Remote control is disabled"))),
+            RcStatus::Disabled
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_remote_control() {
+        let (path, _server) = spawn_mock(vec![Reply::Json(r#"{"ok":true,"data":"[]"}"#.into())]);
+
+        let mut kitty = connect_mock(&path).await;
+        assert_eq!(
+            kitty.check_remote_control().await.unwrap(),
+            RcStatus::Enabled
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_remote_control_no_reply() {
+        let (path, _server) = spawn_mock(vec![]);
+
+        let mut kitty = Kitty::builder()
+            .socket_path(&path)
+            .timeout(Duration::from_millis(50))
+            .connect()
+            .await
+            .unwrap();
+        assert_eq!(
+            kitty.check_remote_control().await.unwrap(),
+            RcStatus::AskPending
+        );
+    }
+
+    #[tokio::test]
+    async fn test_text_sink() {
+        use futures::SinkExt;
+
+        let (path, server) = spawn_mock(vec![]);
+        let kitty = connect_mock(&path).await;
+
+        let mut sink = Box::pin(kitty.text_sink("id:4"));
+        for text in ["one", "two", "three"] {
+            sink.send(text.to_string()).await.unwrap();
+        }
+        sink.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received.len(), 3);
+        for (msg, text) in received.iter().zip(["one", "two", "three"]) {
+            assert_eq!(msg.cmd, "send-text");
+            assert_eq!(msg.no_response, Some(true));
+            let payload = msg.payload.as_ref().unwrap();
+            assert_eq!(payload["match"], "id:4");
+            assert_eq!(payload["data"], format!("text:{}", text));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_previous_tab_and_window() {
+        let (path, server) = spawn_mock(vec![
+            Reply::Json(r#"{"ok":true}"#.into()),
+            Reply::Json(r#"{"ok":true}"#.into()),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        kitty.previous_tab().await.unwrap();
+        kitty.previous_window().await.unwrap();
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        let tab = received[0].payload.as_ref().unwrap();
+        assert_eq!(tab["action"], "goto_tab");
+        assert_eq!(tab["args"], serde_json::json!(["-1"]));
+        let window = received[1].payload.as_ref().unwrap();
+        assert_eq!(window["action"], "nth_window");
+        assert_eq!(window["args"], serde_json::json!(["-1"]));
+    }
+
+    #[tokio::test]
+    async fn test_alternate_tab() {
+        let (path, server) = spawn_mock(vec![
+            Reply::Json(r#"{"ok":true}"#.into()),
+            Reply::Json(
+                r#"{"ok":false,"error":"No matching tabs for expression: recent:1"}"#.into(),
+            ),
+            Reply::Json(r#"{"ok":false,"error":"boom"}"#.into()),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        assert!(kitty.alternate_tab().await.unwrap());
+        assert!(!kitty.alternate_tab().await.unwrap());
+        assert!(matches!(
+            kitty.alternate_tab().await,
+            Err(KittyError::Command(CommandError::KittyError(_, _)))
+        ));
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received[0].cmd, "focus-tab");
+        assert_eq!(received[0].payload.as_ref().unwrap()["match"], "recent:1");
+    }
+
+    #[tokio::test]
+    async fn test_balance_windows() {
+        let (path, server) = spawn_mock(vec![
+            ls_reply(serde_json::json!([{"id": 1, "tabs": [
+                {"id": 4, "windows": [{"id": 9}, {"id": 10}]}
+            ]}])),
+            Reply::Json(r#"{"ok":true}"#.into()),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        kitty.balance_windows("title:editor").await.unwrap();
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(
+            received[0].payload.as_ref().unwrap()["match_tab"],
+            "title:editor"
+        );
+        let payload = received[1].payload.as_ref().unwrap();
+        assert_eq!(payload["action"], "reset_window_sizes");
+        assert_eq!(payload["match_window"], "id:9");
+    }
+
+    #[tokio::test]
+    async fn test_balance_windows_missing_tab() {
+        let (path, server) = spawn_mock(vec![ls_reply(serde_json::json!([]))]);
+
+        let mut kitty = connect_mock(&path).await;
+        let result = kitty.balance_windows("title:nope").await;
+        kitty.close().await.unwrap();
+
+        assert!(matches!(
+            result,
+            Err(KittyError::Command(CommandError::InvalidTabMatch(_)))
+        ));
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_scrollback_pager_input() {
+        let (path, server) = spawn_mock(vec![Reply::Json(
+            r#"{"ok":true,"data":"\u001b[32mold output\u001b[m\r\nwrapped line\r\n$ "}"#.into(),
+        )]);
+
+        let mut kitty = connect_mock(&path).await;
+        let text = kitty.scrollback_pager_input("id:3").await.unwrap();
+        kitty.close().await.unwrap();
+        assert_eq!(text, "\x1b[32mold output\x1b[m\r\nwrapped line\r\n$ ");
+
+        let received = server.await.unwrap();
+        let payload = received[0].payload.as_ref().unwrap();
+        assert_eq!(received[0].cmd, "get-text");
+        assert_eq!(payload["match"], "id:3");
+        assert_eq!(payload["extent"], "all");
+        assert_eq!(payload["ansi"], true);
+        assert_eq!(payload["wrap_markers"], true);
+    }
+
+    #[tokio::test]
+    async fn test_capture_to_file() {
+        let (path, server) = spawn_mock(vec![Reply::Json(
+            r#"{"ok":true,"data":"\u001b[31m$ make\u001b[m\nok\n"}"#.into(),
+        )]);
+        let dir = std::env::temp_dir().join(format!("kitty-rc-capture-{}", std::process::id()));
+        let file = dir.join("nested").join("pane.txt");
+
+        let mut kitty = connect_mock(&path).await;
+        let written = kitty
+            .capture_to_file(
+                "id:2",
+                &file,
+                CaptureOptions {
+                    ansi: true,
+                    extent: Some("all".to_string()),
+                },
+            )
+            .await
+            .unwrap();
+        kitty.close().await.unwrap();
+
+        let contents = std::fs::read_to_string(&file).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(contents, "\x1b[31m$ make\x1b[m\nok\n");
+        assert_eq!(written, contents.len());
+
+        let received = server.await.unwrap();
+        let payload = received[0].payload.as_ref().unwrap();
+        assert_eq!(received[0].cmd, "get-text");
+        assert_eq!(payload["match"], "id:2");
+        assert_eq!(payload["ansi"], true);
+        assert_eq!(payload["extent"], "all");
+    }
+
+    #[tokio::test]
+    async fn test_on_reconnect() {
+        let path = mock_socket_path();
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = tokio::spawn(async move {
+            let mut connections = Vec::new();
+            for _ in 0..3 {
+                connections.push(listener.accept().await.unwrap().0);
+            }
+            connections.len()
+        });
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut kitty = Kitty::builder()
+            .socket_path(&path)
+            .on_reconnect({
+                let count = count.clone();
+                move || {
+                    count.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .connect()
+            .await
+            .unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+
+        kitty.reconnect().await.unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        kitty.reconnect().await.unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        assert_eq!(server.await.unwrap(), 3);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_user_vars() {
+        let (path, server) = spawn_mock(vec![
+            ls_reply(serde_json::json!([{"tabs": [{"windows": [
+                {"id": 1, "user_vars": {"project": "kitty-rc", "branch": "main"}}
+            ]}]}])),
+            ls_reply(serde_json::json!([{"tabs": [{"windows": [{"id": 2}]}]}])),
+            ls_reply(serde_json::json!([])),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        let vars = kitty.user_vars("id:1").await.unwrap();
+        assert_eq!(
+            vars.iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("branch", "main"), ("project", "kitty-rc")]
+        );
+        assert!(kitty.user_vars("id:2").await.unwrap().is_empty());
+        assert!(matches!(
+            kitty.user_vars("id:3").await,
+            Err(KittyError::Command(CommandError::InvalidWindowMatch(_)))
+        ));
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received[0].payload.as_ref().unwrap()["match"], "id:1");
+    }
+
+    #[tokio::test]
+    async fn test_set_user_var_everywhere() {
+        let (path, server) = spawn_mock(vec![Reply::Json(r#"{"ok":true}"#.into())]);
+
+        let mut kitty = connect_mock(&path).await;
+        kitty
+            .set_user_var_everywhere("theme", "dark")
+            .await
+            .unwrap();
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        let payload = received[0].payload.as_ref().unwrap();
+        assert_eq!(received[0].cmd, "set-user-vars");
+        assert_eq!(payload["var"], serde_json::json!(["theme=dark"]));
+        assert_eq!(payload["match"], "all");
+    }
+
+    #[tokio::test]
+    async fn test_is_at_prompt() {
+        let tree = |window: serde_json::Value| {
+            ls_reply(serde_json::json!([{"tabs": [{"windows": [window]}]}]))
+        };
+        let (path, server) = spawn_mock(vec![
+            tree(serde_json::json!({"id": 1, "at_prompt": true})),
+            tree(serde_json::json!({"id": 2, "at_prompt": false})),
+            tree(serde_json::json!({"id": 3})),
+            ls_reply(serde_json::json!([])),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        assert!(kitty.is_at_prompt("id:1").await.unwrap());
+        assert!(!kitty.is_at_prompt("id:2").await.unwrap());
+        assert!(matches!(
+            kitty.is_at_prompt("id:3").await,
+            Err(KittyError::Command(CommandError::ValidationError(_)))
+        ));
+        assert!(matches!(
+            kitty.is_at_prompt("id:4").await,
+            Err(KittyError::Command(CommandError::InvalidWindowMatch(_)))
+        ));
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received[1].payload.as_ref().unwrap()["match"], "id:2");
+    }
+
+    #[tokio::test]
+    async fn test_focused_window_id() {
+        let (path, server) = spawn_mock(vec![
+            ls_reply(serde_json::json!([
+                {"id": 1, "tabs": [{"windows": [
+                    {"id": 3, "is_focused": false, "title": "a"},
+                    {"id": 4, "is_focused": true, "env": {"TERM": "xterm-kitty"}}
+                ]}]}
+            ])),
+            ls_reply(serde_json::json!([])),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        assert_eq!(kitty.focused_window_id().await.unwrap(), Some(WindowId(4)));
+        assert_eq!(kitty.focused_window_id().await.unwrap(), None);
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received[0].cmd, "ls");
+        assert_eq!(
+            received[0].payload.as_ref().unwrap()["match"],
+            "state:focused"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_text_only_at_prompt() {
+        let tree = |at_prompt: bool| {
+            ls_reply(serde_json::json!([{"tabs": [{"windows": [
+                {"id": 1, "at_prompt": at_prompt, "is_focused": true}
+            ]}]}]))
+        };
+        let (path, server) = spawn_mock(vec![
+            tree(true),
+            Reply::Json(r#"{"ok": true}"#.to_string()),
+            tree(false),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        let command = || {
+            SendTextCommand::new("make\n")
+                .only_at_prompt(true)
+                .build()
+                .unwrap()
+        };
+        assert!(kitty.execute(&command()).await.unwrap().ok);
+        assert!(matches!(
+            kitty.execute(&command()).await,
+            Err(KittyError::Command(CommandError::ValidationError(_)))
+        ));
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        let cmds: Vec<_> = received.iter().map(|msg| msg.cmd.as_str()).collect();
+        assert_eq!(cmds, ["ls", "send-text", "ls"]);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_send_text_only_at_prompt() {
+        let busy = || {
+            ls_reply(serde_json::json!([{"tabs": [{"windows": [
+                {"id": 1, "at_prompt": false, "is_focused": true}
+            ]}]}]))
+        };
+        let (path, server) = spawn_mock(vec![busy(), busy(), busy()]);
+
+        let mut kitty = connect_mock(&path).await;
+        let message = SendTextCommand::new(format!("text:{}", "x".repeat(5000)))
+            .only_at_prompt(true)
+            .build()
+            .unwrap();
+        assert!(message.needs_streaming());
+        assert!(matches!(
+            kitty.execute_all(&message).await,
+            Err(KittyError::Command(CommandError::ValidationError(_)))
+        ));
+        assert!(matches!(
+            kitty.send_command(message.clone()).await,
+            Err(KittyError::Command(CommandError::ValidationError(_)))
+        ));
+        assert!(matches!(
+            kitty.execute_timed(&message).await,
+            Err(KittyError::Command(CommandError::ValidationError(_)))
+        ));
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        let cmds: Vec<_> = received.iter().map(|msg| msg.cmd.as_str()).collect();
+        assert_eq!(cmds, ["ls", "ls", "ls"]);
+    }
+
+    #[tokio::test]
+    async fn test_receive_prefix_split_across_reads() {
+        let mut pieces: Vec<Vec<u8>> = b"\x1bP@kitty-cmd".iter().map(|b| vec![*b]).collect();
+        pieces.push(br#"{"ok": true, "data": "done"}"#.to_vec());
+        pieces.push(b"\x1b\\".to_vec());
+        let (path, server) = spawn_mock(vec![Reply::Pieces(pieces)]);
+
+        let mut kitty = connect_mock(&path).await;
+        let response = kitty
+            .execute(&LsCommand::new().build().unwrap())
+            .await
+            .unwrap();
+        assert!(response.ok);
+        assert_eq!(response.data.unwrap(), "done");
+        kitty.close().await.unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_receive_skips_progress_envelopes() {
+        let frame = |json: &str| format!("\x1bP@kitty-cmd{}\x1b\\", json).into_bytes();
+        let mut together = frame(r#"{"progress": 0.5}"#);
+        together.extend(frame(r#"{"ok": true, "async_id": "a1"}"#));
+        let (path, server) = spawn_mock(vec![Reply::Pieces(vec![
+            frame(r#"{"ok": true, "progress": "started"}"#),
+            together,
+            frame(r#"{"ok": true, "data": "7", "async_id": "a1"}"#),
+        ])]);
+
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut kitty = Kitty::builder()
+            .socket_path(&path)
+            .on_progress({
+                let seen = seen.clone();
+                move |kind, _| seen.lock().unwrap().push(kind)
+            })
+            .connect()
+            .await
+            .unwrap();
+        let response = kitty
+            .execute(&LsCommand::new().build().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.data.unwrap(), "7");
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                ResponseKind::Progress,
+                ResponseKind::Progress,
+                ResponseKind::Ack
+            ]
+        );
+        kitty.close().await.unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_set_marker() {
+        let (path, server) = spawn_mock(vec![
+            Reply::Json(r#"{"ok": true}"#.to_string()),
+            Reply::Json(r#"{"ok": true}"#.to_string()),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        kitty
+            .set_marker("id:1", "text 1 ERROR", true)
+            .await
+            .unwrap();
+        kitty
+            .set_marker("id:1", "text 1 ERROR", false)
+            .await
+            .unwrap();
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received[0].cmd, "create-marker");
+        let payload = received[0].payload.as_ref().unwrap();
+        assert_eq!(payload["match"], "id:1");
+        assert_eq!(payload["marker_spec"], "text 1 ERROR");
+
+        assert_eq!(received[1].cmd, "remove-marker");
+        let payload = received[1].payload.as_ref().unwrap();
+        assert_eq!(payload["match"], "id:1");
+        assert!(payload.get("marker_spec").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_focus_by_title() {
+        let (path, server) = spawn_mock(vec![
+            Reply::Json(r#"{"ok": true}"#.to_string()),
+            Reply::Json(
+                r#"{"ok": false, "error": "No matching windows for expression: title:nope"}"#
+                    .to_string(),
+            ),
+            Reply::Json(r#"{"ok": false, "error": "boom"}"#.to_string()),
+        ]);
+
+        let mut kitty = connect_mock(&path).await;
+        assert!(kitty.focus_by_title("^vim (main)$").await.unwrap());
+        assert!(!kitty.focus_by_title("nope").await.unwrap());
+        assert!(matches!(
+            kitty.focus_by_title("x").await,
+            Err(KittyError::Command(CommandError::KittyError(_, _)))
+        ));
+        kitty.close().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received[0].cmd, "focus-window");
+        assert_eq!(
+            received[0].payload.as_ref().unwrap()["match"],
+            r#"title:"^vim (main)$""#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_interactive_commands_skip_read_timeout() {
+        let delay = Duration::from_millis(150);
+        let (path, server) = spawn_mock(vec![
+            Reply::Delayed(delay, r#"{"ok": true, "data": {"id": 3}}"#.to_string()),
+            Reply::Delayed(delay, r#"{"ok": true, "data": "[]"}"#.to_string()),
+        ]);
+
+        let mut kitty = Kitty::builder()
+            .socket_path(&path)
+            .timeout(Duration::from_millis(50))
+            .connect()
+            .await
+            .unwrap();
+
+        let select = SelectWindowCommand::new().build().unwrap();
+        assert!(select.interactive);
+        assert!(kitty.execute(&select).await.unwrap().ok);
+
+        let ls = LsCommand::new().build().unwrap();
+        assert!(!ls.interactive);
+        assert!(matches!(
+            kitty.execute(&ls).await,
+            Err(KittyError::Connection(ConnectionError::TimeoutError(_)))
+        ));
+        kitty.close().await.unwrap();
+        server.await.unwrap();
+    }
+
+    #[test]
+    fn test_resolve_public_key_without_pubkey_db() {
+        let missing =
+            |pid| KittyBuilder::query_public_key_database("kitty-pubkey-db-not-installed", pid);
+
+        let resolved =
+            KittyBuilder::resolve_public_key(None, "/tmp/kitty-1234.sock", missing).unwrap();
+        assert_eq!(resolved, None);
+
+        let explicit = Some("1:explicit".to_string());
+        let resolved =
+            KittyBuilder::resolve_public_key(explicit.clone(), "/tmp/kitty-1234.sock", missing)
+                .unwrap();
+        assert_eq!(resolved, explicit);
+    }
+
+    #[test]
+    fn test_query_public_key_database_unrunnable() {
+        // Present but not executable: a real failure, not a missing helper.
+        let path = std::env::temp_dir().join(format!("kitty-rc-pubkey-db-{}", std::process::id()));
+        std::fs::write(&path, "#!/bin/sh\n").unwrap();
+        let result = KittyBuilder::query_public_key_database(path.to_str().unwrap(), 1234);
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(
+            result,
+            Err(EncryptionError::PublicKeyDatabaseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_public_key_cached() {
+        let calls = AtomicUsize::new(0);
+        let lookup = |_pid| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Ok(Some("1:cached".to_string()))
+        };
+        let socket = "/tmp/kitty-4000000001.sock";
+
+        for _ in 0..2 {
+            let resolved = KittyBuilder::resolve_public_key(None, socket, lookup).unwrap();
+            assert_eq!(resolved.as_deref(), Some("1:cached"));
+        }
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        clear_pubkey_cache();
+        KittyBuilder::resolve_public_key(None, socket, lookup).unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_append_pubkey_entry() {
+        let db_path = std::env::temp_dir().join(format!(
+            "kitty-rc-test-pubkey-{}.tsv",
+            MOCK_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&db_path);
+
+        append_pubkey_entry(&db_path, 1234, Some(7), "1:abc").unwrap();
+        append_pubkey_entry(&db_path, 1234, None, "1:def").unwrap();
+
+        let content = std::fs::read_to_string(&db_path).unwrap();
+        let lines: Vec<Vec<&str>> = content.lines().map(|l| l.split('\t').collect()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0][..3], ["1234", "7", "1:abc"]);
+        assert_eq!(lines[1][..3], ["1234", "", "1:def"]);
+        assert!(lines[0][3].parse::<u64>().unwrap() > 0);
+        let _ = std::fs::remove_file(&db_path);
+    }
 }
@@ -0,0 +1,157 @@
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// An RGB color, written the way kitty's config does: `#rrggbb`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Parse `#rrggbb` or the short `#rgb`, with or without the `#`.
+    pub fn from_hex(value: &str) -> Option<Self> {
+        let hex = value.strip_prefix('#').unwrap_or(value);
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+        match hex.len() {
+            6 => Some(Self::new(
+                channel(&hex[0..2])?,
+                channel(&hex[2..4])?,
+                channel(&hex[4..6])?,
+            )),
+            3 => Some(Self::new(
+                channel(&hex[0..1])? * 0x11,
+                channel(&hex[1..2])? * 0x11,
+                channel(&hex[2..3])? * 0x11,
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Color settings in kitty's config other than the `color0`..`color255`
+/// palette, as accepted by `set-colors`.
+const THEME_KEYS: &[&str] = &[
+    "active_border_color",
+    "active_tab_background",
+    "active_tab_foreground",
+    "background",
+    "bell_border_color",
+    "cursor",
+    "cursor_text_color",
+    "foreground",
+    "inactive_border_color",
+    "inactive_tab_background",
+    "inactive_tab_foreground",
+    "mark1_background",
+    "mark1_foreground",
+    "mark2_background",
+    "mark2_foreground",
+    "mark3_background",
+    "mark3_foreground",
+    "selection_background",
+    "selection_foreground",
+    "tab_bar_background",
+    "tab_bar_margin_color",
+    "url_color",
+    "visual_bell_color",
+];
+
+impl Color {
+    /// Parse a kitty theme file (`color0 #000000` lines, as in kitty's
+    /// `.conf` themes) into a colors map for
+    /// [`SetColorsCommand`](crate::SetColorsCommand).
+    ///
+    /// Comments, blank lines and directives that aren't colors (fonts,
+    /// `include`, ...) are skipped. Hex values are written as `#rrggbb`,
+    /// anything else (`none`, color names) is kept as is.
+    pub fn parse_theme(contents: &str) -> Map<String, Value> {
+        contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once(char::is_whitespace))
+            .filter(|(key, _)| is_theme_key(key))
+            .map(|(key, value)| {
+                let value = value.trim();
+                let value =
+                    Color::from_hex(value).map_or_else(|| value.to_string(), |c| c.to_string());
+                (key.to_string(), Value::String(value))
+            })
+            .collect()
+    }
+}
+
+fn is_theme_key(key: &str) -> bool {
+    THEME_KEYS.contains(&key)
+        || key
+            .strip_prefix("color")
+            .and_then(|n| n.parse::<u8>().ok())
+            .is_some()
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_hex() {
+        assert_eq!(Color::from_hex("#ff8000"), Some(Color::new(255, 128, 0)));
+        assert_eq!(Color::from_hex("FF8000"), Some(Color::new(255, 128, 0)));
+        assert_eq!(Color::from_hex("#f80"), Some(Color::new(255, 136, 0)));
+        assert_eq!(Color::from_hex("#xyz"), None);
+        assert_eq!(Color::from_hex("#ff80"), None);
+        assert_eq!(Color::from_hex(""), None);
+    }
+
+    #[test]
+    fn test_parse_theme() {
+        let theme = "\
+## name: Tiny
+# a comment
+foreground            #dddddd
+background #000
+selection_foreground  none
+cursor_text_color background
+
+color0   #1D1F21
+color255 #eeeeee
+color256 #ffffff
+font_size 12
+include other.conf
+";
+        let colors = Color::parse_theme(theme);
+
+        let expected: Map<String, Value> = serde_json::from_value(serde_json::json!({
+            "foreground": "#dddddd",
+            "background": "#000000",
+            "selection_foreground": "none",
+            "cursor_text_color": "background",
+            "color0": "#1d1f21",
+            "color255": "#eeeeee"
+        }))
+        .unwrap();
+        assert_eq!(colors, expected);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Color::new(255, 128, 0).to_string(), "#ff8000");
+        assert_eq!(Color::from_hex("ABC").unwrap().to_string(), "#aabbcc");
+    }
+}
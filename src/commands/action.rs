@@ -1,8 +1,12 @@
+use crate::ids::WindowId;
+use crate::match_spec::MatchSpec;
 use crate::protocol::KittyMessage;
 
 pub struct ActionCommand {
     action: String,
     args: Vec<String>,
+    match_spec: Option<String>,
+    self_window: bool,
 }
 
 impl ActionCommand {
@@ -10,6 +14,8 @@ impl ActionCommand {
         Self {
             action: action.into(),
             args: Vec::new(),
+            match_spec: None,
+            self_window: false,
         }
     }
 
@@ -25,6 +31,20 @@ impl ActionCommand {
         self
     }
 
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
+        self
+    }
+
+    pub fn window_id(self, id: impl Into<WindowId>) -> Self {
+        self.match_spec(MatchSpec::id(id))
+    }
+
+    pub fn self_window(mut self, value: bool) -> Self {
+        self.self_window = value;
+        self
+    }
+
     pub fn build(self) -> Result<KittyMessage, crate::error::CommandError> {
         let mut payload = serde_json::Map::new();
         payload.insert("action".to_string(), serde_json::Value::String(self.action));
@@ -41,6 +61,17 @@ impl ActionCommand {
             );
         }
 
+        if let Some(match_spec) = self.match_spec {
+            payload.insert(
+                "match_window".to_string(),
+                serde_json::Value::String(match_spec),
+            );
+        }
+
+        if self.self_window {
+            payload.insert("self".to_string(), serde_json::Value::Bool(true));
+        }
+
         Ok(KittyMessage::new("send_key", vec![0, 14, 2])
             .payload(serde_json::Value::Object(payload)))
     }
@@ -610,6 +641,34 @@ mod tests {
         assert!(msg.payload.is_some());
     }
 
+    #[test]
+    fn test_action_command_with_match() {
+        let msg = GotoLayoutAction::new("tall")
+            .match_spec("id:3")
+            .build()
+            .unwrap();
+        let payload = msg.payload.unwrap();
+        assert_eq!(payload["match_window"], "id:3");
+        assert_eq!(payload["action"], "goto_layout");
+    }
+
+    #[test]
+    fn test_action_command_window_id() {
+        let msg = ActionCommand::new("close_window")
+            .window_id(7)
+            .build()
+            .unwrap();
+        assert_eq!(msg.payload.unwrap()["match_window"], "id:7");
+    }
+
+    #[test]
+    fn test_action_command_without_match() {
+        let msg = ActionCommand::new("quit").build().unwrap();
+        let payload = msg.payload.unwrap();
+        assert!(payload.get("match_window").is_none());
+        assert!(payload.get("self").is_none());
+    }
+
     #[test]
     fn test_quit_action() {
         let cmd = QuitAction::new().build();
@@ -0,0 +1,192 @@
+use crate::commands::action::ActionCommand;
+use crate::commands::style::FontInfo;
+use crate::error::CommandError;
+use crate::protocol::{KittyMessage, KittyResponse};
+
+/// kitty's default `background_opacity`.
+pub const DEFAULT_BACKGROUND_OPACITY: f32 = 1.0;
+
+/// kitty's default `allow_remote_control`.
+pub const DEFAULT_ALLOW_REMOTE_CONTROL: &str = "no";
+
+/// Runs kitty's `debug_config` action, which reports the effective
+/// configuration as text.
+#[derive(Default)]
+pub struct DebugConfigCommand;
+
+impl DebugConfigCommand {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn build(self) -> Result<KittyMessage, CommandError> {
+        ActionCommand::new("debug_config").build()
+    }
+
+    /// Parse the text returned by kitty. Returns `None` when the response
+    /// carries no text, which happens with kitty versions that only show
+    /// the output in a window.
+    pub fn parse_response(response: &KittyResponse) -> Option<ConfigSummary> {
+        let output = response.data.as_ref()?.as_str()?;
+        Some(ConfigSummary::parse(output))
+    }
+}
+
+/// Commonly wanted settings extracted from `debug_config` output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigSummary {
+    pub font: FontInfo,
+    pub background_opacity: f32,
+    /// Layout names, or `["*"]` (kitty's default) for all layouts.
+    pub enabled_layouts: Vec<String>,
+    /// The raw `allow_remote_control` value, e.g. `yes`, `socket-only`.
+    pub allow_remote_control: String,
+}
+
+impl Default for ConfigSummary {
+    fn default() -> Self {
+        Self {
+            font: FontInfo::default(),
+            background_opacity: DEFAULT_BACKGROUND_OPACITY,
+            enabled_layouts: vec!["*".to_string()],
+            allow_remote_control: DEFAULT_ALLOW_REMOTE_CONTROL.to_string(),
+        }
+    }
+}
+
+impl ConfigSummary {
+    /// Extract the summary from `debug_config` output.
+    ///
+    /// kitty only lists options that differ from the defaults, so anything
+    /// missing keeps its default. Unknown lines, headings and terminal
+    /// escape codes are ignored, and unparseable values are skipped.
+    pub fn parse(output: &str) -> Self {
+        let mut summary = Self {
+            font: FontInfo::from_debug_config(output),
+            ..Self::default()
+        };
+
+        for (key, value) in debug_config_options(output) {
+            match key.as_str() {
+                "background_opacity" => {
+                    if let Ok(opacity) = value.parse() {
+                        summary.background_opacity = opacity;
+                    }
+                }
+                "enabled_layouts" => {
+                    let layouts = parse_list(&value);
+                    if !layouts.is_empty() {
+                        summary.enabled_layouts = layouts;
+                    }
+                }
+                "allow_remote_control" => summary.allow_remote_control = value,
+                _ => {}
+            }
+        }
+
+        summary
+    }
+}
+
+/// Yield `(option, value)` pairs from `debug_config` output, skipping
+/// headings and other lines that don't look like `name   value`.
+pub(crate) fn debug_config_options(output: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    output.lines().filter_map(|line| {
+        let line = strip_escape_codes(line);
+        let (key, value) = line.trim().split_once(char::is_whitespace)?;
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+            return None;
+        }
+        Some((key.to_string(), value.trim().to_string()))
+    })
+}
+
+/// Remove CSI sequences such as the colors kitty uses for headings.
+fn strip_escape_codes(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// Parse either kitty's conf form (`tall,stack`) or a Python list repr
+/// (`['tall', 'stack']`).
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .trim_start_matches(['[', '('])
+        .trim_end_matches([']', ')'])
+        .split(',')
+        .map(|item| item.trim().trim_matches(['\'', '"']).to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEBUG_CONFIG_SAMPLE: &str = "\x1b[1mkitty 0.36.4\x1b[m created by \x1b[32mKovid Goyal\x1b[m
+Linux 6.8.0 #1 SMP x86_64
+Running under: \x1b[32mWayland\x1b[m
+
+Loaded config files:
+  /home/user/.config/kitty/kitty.conf
+
+Config options different from defaults:
+\x1b[1mallow_remote_control\x1b[m      socket-only
+\x1b[1mbackground_opacity\x1b[m        0.85
+\x1b[1menabled_layouts\x1b[m           ['tall', 'stack']
+\x1b[1mfont_family\x1b[m               family=\"JetBrains Mono\" postscript_name=JetBrainsMono-Regular
+\x1b[1mfont_size\x1b[m                 13.5
+";
+
+    #[test]
+    fn test_debug_config_command_basic() {
+        let msg = DebugConfigCommand::new().build().unwrap();
+        assert_eq!(msg.cmd, "send_key");
+        assert_eq!(msg.payload.unwrap()["action"], "debug_config");
+    }
+
+    #[test]
+    fn test_config_summary_parse() {
+        let summary = ConfigSummary::parse(DEBUG_CONFIG_SAMPLE);
+        assert_eq!(summary.font.family, "JetBrains Mono");
+        assert_eq!(summary.font.size, 13.5);
+        assert_eq!(summary.background_opacity, 0.85);
+        assert_eq!(summary.enabled_layouts, vec!["tall", "stack"]);
+        assert_eq!(summary.allow_remote_control, "socket-only");
+    }
+
+    #[test]
+    fn test_config_summary_defaults() {
+        let summary = ConfigSummary::parse("enabled_layouts splits,fat\nbackground_opacity oops\n");
+        assert_eq!(summary.enabled_layouts, vec!["splits", "fat"]);
+        assert_eq!(summary.background_opacity, DEFAULT_BACKGROUND_OPACITY);
+        assert_eq!(summary.allow_remote_control, "no");
+        assert_eq!(summary.font, FontInfo::default());
+    }
+
+    #[test]
+    fn test_parse_response_without_text() {
+        let response = KittyResponse {
+            ok: true,
+            data: None,
+            error: None,
+        };
+        assert!(DebugConfigCommand::parse_response(&response).is_none());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::command::CommandBuilder;
 use crate::error::CommandError;
+use crate::match_spec::MatchSpec;
 use crate::protocol::KittyMessage;
 
 pub struct GotoLayoutCommand {
@@ -15,8 +16,8 @@ impl GotoLayoutCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -57,8 +58,8 @@ impl SetEnabledLayoutsCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -115,8 +116,8 @@ impl LastUsedLayoutCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -1,24 +1,179 @@
 pub mod action;
+pub mod config;
 pub mod layout;
 pub mod process;
 pub mod style;
 pub mod tab;
 pub mod window;
 
+use crate::error::CommandError;
+use crate::protocol::KittyMessage;
+
 pub use action::*;
+pub use config::{ConfigSummary, DebugConfigCommand};
 pub use layout::{GotoLayoutCommand, LastUsedLayoutCommand, SetEnabledLayoutsCommand};
 pub use process::{
-    DisableLigaturesCommand, EnvCommand, KittenCommand, LaunchCommand, LoadConfigCommand,
+    DisableLigaturesCommand, EnvCommand, EnvMap, KittenCommand, LaunchCommand, LoadConfigCommand,
     ResizeOSWindowCommand, RunCommand, SetUserVarsCommand, SignalChildCommand,
 };
 pub use style::{
-    GetColorsCommand, SetBackgroundImageCommand, SetBackgroundOpacityCommand, SetColorsCommand,
-    SetFontSizeCommand, SetSpacingCommand, SetTabColorCommand,
+    FontInfo, GetColorsCommand, SetBackgroundImageCommand, SetBackgroundOpacityCommand,
+    SetColorsCommand, SetFontSizeCommand, SetSpacingCommand, SetTabColorCommand,
+};
+pub use tab::{
+    CloseTabCommand, DetachTabCommand, FocusTabCommand, NewTabCommand, SetTabTitleCommand,
 };
-pub use tab::{CloseTabCommand, DetachTabCommand, FocusTabCommand, SetTabTitleCommand};
 pub use window::{
     CloseWindowCommand, CreateMarkerCommand, DetachWindowCommand, FocusWindowCommand,
-    GetTextCommand, LsCommand, NewWindowCommand, RemoveMarkerCommand, ResizeWindowCommand,
-    ScrollWindowCommand, SelectWindowCommand, SendKeyCommand, SendTextCommand,
-    SetWindowLogoCommand, SetWindowTitleCommand,
+    GetTextCommand, LineEnding, LsCommand, NewWindowCommand, RemoveMarkerCommand,
+    ResizeWindowCommand, ScrollWindowCommand, SelectWindowCommand, SendKeyCommand, SendTextCommand,
+    SetOsWindowTitleCommand, SetWindowLogoCommand, SetWindowTitleCommand,
 };
+
+/// Let each builder be passed wherever a `TryInto<KittyMessage>` is taken,
+/// e.g. [`Kitty::run`](crate::Kitty::run), building it on the way.
+macro_rules! impl_try_from_builder {
+    ($($builder:ty),* $(,)?) => {
+        $(
+            impl TryFrom<$builder> for KittyMessage {
+                type Error = CommandError;
+
+                fn try_from(builder: $builder) -> Result<Self, Self::Error> {
+                    builder.build()
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_builder!(
+    ActionCommand,
+    CloseTabCommand,
+    CloseWindowCommand,
+    CreateMarkerCommand,
+    DebugConfigCommand,
+    DetachTabCommand,
+    DetachWindowCommand,
+    DisableLigaturesCommand,
+    EnvCommand,
+    FocusTabCommand,
+    FocusWindowCommand,
+    GetColorsCommand,
+    GetTextCommand,
+    GotoLayoutCommand,
+    KittenCommand,
+    LastUsedLayoutCommand,
+    LaunchCommand,
+    LoadConfigCommand,
+    LsCommand,
+    NewTabCommand,
+    NewWindowCommand,
+    RemoveMarkerCommand,
+    ResizeOSWindowCommand,
+    ResizeWindowCommand,
+    RunCommand,
+    ScrollWindowCommand,
+    SelectWindowCommand,
+    SendKeyCommand,
+    SendTextCommand,
+    SetBackgroundImageCommand,
+    SetBackgroundOpacityCommand,
+    SetColorsCommand,
+    SetEnabledLayoutsCommand,
+    SetFontSizeCommand,
+    SetOsWindowTitleCommand,
+    SetSpacingCommand,
+    SetTabColorCommand,
+    SetTabTitleCommand,
+    SetUserVarsCommand,
+    SetWindowLogoCommand,
+    SetWindowTitleCommand,
+    SignalChildCommand,
+);
+
+/// Every command builder in this crate with the kitty remote control command
+/// it emits, sorted by builder name. Builders in [`action`] are left out:
+/// they all run kitty actions through `send_key`.
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("CloseTabCommand", "close-tab"),
+    ("CloseWindowCommand", "close-window"),
+    ("CreateMarkerCommand", "create-marker"),
+    ("DebugConfigCommand", "send_key"),
+    ("DetachTabCommand", "detach-tab"),
+    ("DetachWindowCommand", "detach-window"),
+    ("DisableLigaturesCommand", "disable-ligatures"),
+    ("EnvCommand", "env"),
+    ("FocusTabCommand", "focus-tab"),
+    ("FocusWindowCommand", "focus-window"),
+    ("GetColorsCommand", "get-colors"),
+    ("GetTextCommand", "get-text"),
+    ("GotoLayoutCommand", "goto-layout"),
+    ("KittenCommand", "kitten"),
+    ("LastUsedLayoutCommand", "last-used-layout"),
+    ("LaunchCommand", "launch"),
+    ("LoadConfigCommand", "load-config"),
+    ("LsCommand", "ls"),
+    ("NewTabCommand", "launch"),
+    ("NewWindowCommand", "new-window"),
+    ("RemoveMarkerCommand", "remove-marker"),
+    ("ResizeOSWindowCommand", "resize-os-window"),
+    ("ResizeWindowCommand", "resize-window"),
+    ("RunCommand", "run"),
+    ("ScrollWindowCommand", "scroll-window"),
+    ("SelectWindowCommand", "select-window"),
+    ("SendKeyCommand", "send-key"),
+    ("SendTextCommand", "send-text"),
+    ("SetBackgroundImageCommand", "set-background-image"),
+    ("SetBackgroundOpacityCommand", "set-background-opacity"),
+    ("SetColorsCommand", "set-colors"),
+    ("SetEnabledLayoutsCommand", "set-enabled-layouts"),
+    ("SetFontSizeCommand", "set-font-size"),
+    ("SetOsWindowTitleCommand", "set-window-title"),
+    ("SetSpacingCommand", "set-spacing"),
+    ("SetTabColorCommand", "set-tab-color"),
+    ("SetTabTitleCommand", "set-tab-title"),
+    ("SetUserVarsCommand", "set-user-vars"),
+    ("SetWindowLogoCommand", "set-window-logo"),
+    ("SetWindowTitleCommand", "set-window-title"),
+    ("SignalChildCommand", "signal-child"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kitty_name(builder: &str) -> &'static str {
+        COMMANDS
+            .iter()
+            .find(|(name, _)| *name == builder)
+            .map(|(_, cmd)| *cmd)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_commands_list() {
+        assert!(!COMMANDS.is_empty());
+        assert!(COMMANDS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+        assert_eq!(
+            kitty_name("LsCommand"),
+            LsCommand::new().build().unwrap().cmd
+        );
+        assert_eq!(
+            kitty_name("SendTextCommand"),
+            SendTextCommand::new("x").build().unwrap().cmd
+        );
+        assert_eq!(
+            kitty_name("NewTabCommand"),
+            NewTabCommand::new().build().unwrap().cmd
+        );
+        assert_eq!(
+            kitty_name("DebugConfigCommand"),
+            DebugConfigCommand::new().build().unwrap().cmd
+        );
+        assert_eq!(
+            kitty_name("SetFontSizeCommand"),
+            SetFontSizeCommand::new(12).build().unwrap().cmd
+        );
+    }
+}
@@ -1,10 +1,12 @@
 use crate::command::CommandBuilder;
 use crate::error::CommandError;
-use crate::protocol::KittyMessage;
-use serde::Deserialize;
+use crate::ids::WindowId;
+use crate::match_spec::MatchSpec;
+use crate::protocol::{KittyMessage, KittyResponse};
+use serde::{Deserialize, Serialize};
 use serde_json::Map;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ProcessInfo {
     pub pid: Option<u64>,
     #[serde(default)]
@@ -12,6 +14,49 @@ pub struct ProcessInfo {
     pub cwd: Option<String>,
 }
 
+/// Environment variables for [`RunCommand`], [`LaunchCommand`] and
+/// [`EnvCommand`]. kitty expects every value to be a string, which this
+/// type guarantees.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EnvMap(Map<String, serde_json::Value>);
+
+impl EnvMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.0
+            .insert(key.into(), serde_json::Value::String(value.into()));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<EnvMap> for Map<String, serde_json::Value> {
+    fn from(env: EnvMap) -> Self {
+        env.0
+    }
+}
+
+impl TryFrom<Map<String, serde_json::Value>> for EnvMap {
+    type Error = CommandError;
+
+    /// Accept a raw map only if every value is already a string.
+    fn try_from(map: Map<String, serde_json::Value>) -> Result<Self, Self::Error> {
+        if let Some((key, _)) = map.iter().find(|(_, value)| !value.is_string()) {
+            return Err(CommandError::InvalidParameter(
+                "env".to_string(),
+                format!("value of {} must be a string", key),
+            ));
+        }
+        Ok(Self(map))
+    }
+}
+
 pub struct RunCommand {
     data: Option<String>,
     cmdline: Option<String>,
@@ -41,8 +86,8 @@ impl RunCommand {
         self
     }
 
-    pub fn env(mut self, value: Map<String, serde_json::Value>) -> Self {
-        self.env = Some(value);
+    pub fn env(mut self, value: impl Into<Map<String, serde_json::Value>>) -> Self {
+        self.env = Some(value.into());
         self
     }
 
@@ -93,6 +138,7 @@ impl RunCommand {
 
 pub struct KittenCommand {
     args: Option<String>,
+    kitten: Option<(&'static str, Vec<String>)>,
     match_spec: Option<String>,
 }
 
@@ -100,24 +146,67 @@ impl KittenCommand {
     pub fn new() -> Self {
         Self {
             args: None,
+            kitten: None,
             match_spec: None,
         }
     }
 
+    /// Run the `hints` kitten with extra arguments, e.g. `["--type", "url"]`.
+    pub fn hints<I, S>(args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::typed("hints", args.into_iter().map(Into::into).collect())
+    }
+
+    pub fn unicode_input() -> Self {
+        Self::typed("unicode_input", Vec::new())
+    }
+
+    /// Switch to the theme called `theme_name`, e.g. `"Tokyo Night"`.
+    pub fn themes(theme_name: impl Into<String>) -> Self {
+        Self::typed("themes", vec![theme_name.into()])
+    }
+
+    /// Connect to `host` (`[user@]host`) with the `ssh` kitten.
+    pub fn ssh(host: impl Into<String>) -> Self {
+        Self::typed("ssh", vec![host.into()])
+    }
+
+    pub fn clipboard() -> Self {
+        Self::typed("clipboard", Vec::new())
+    }
+
+    fn typed(kitten: &'static str, args: Vec<String>) -> Self {
+        Self {
+            kitten: Some((kitten, args)),
+            ..Self::new()
+        }
+    }
+
+    /// Raw argument string, kitten name first (e.g. `"diff a b"`). Replaces
+    /// anything set by the typed constructors.
     pub fn args(mut self, value: impl Into<String>) -> Self {
         self.args = Some(value.into());
+        self.kitten = None;
         self
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
     pub fn build(self) -> Result<KittyMessage, CommandError> {
         let mut payload = Map::new();
 
-        if let Some(args) = self.args {
+        let args = match self.kitten {
+            Some((kitten, args)) => Some(kitten_args(kitten, args)?),
+            None => self.args,
+        };
+
+        if let Some(args) = args {
             payload.insert("args".to_string(), serde_json::Value::String(args));
         }
 
@@ -127,8 +216,48 @@ impl KittenCommand {
 
         Ok(CommandBuilder::new("kitten")
             .payload(serde_json::Value::Object(payload))
-            .build())
+            .build()
+            .interactive(true))
+    }
+}
+
+/// Validate the arguments of a typed kitten and join them into kitty's
+/// argument string, single-quoting any that contain whitespace or quotes.
+fn kitten_args(kitten: &str, args: Vec<String>) -> Result<String, CommandError> {
+    match kitten {
+        "themes" if args[0].trim().is_empty() => {
+            return Err(CommandError::MissingParameter(
+                "theme_name".to_string(),
+                "kitten themes".to_string(),
+            ));
+        }
+        "ssh" if args[0].is_empty() => {
+            return Err(CommandError::MissingParameter(
+                "host".to_string(),
+                "kitten ssh".to_string(),
+            ));
+        }
+        "ssh" if args[0].starts_with('-') || args[0].contains(char::is_whitespace) => {
+            return Err(CommandError::InvalidParameter(
+                "host".to_string(),
+                format!("'{}' is not a host name", args[0]),
+            ));
+        }
+        _ => {}
     }
+
+    let quoted = args.iter().map(|arg| {
+        if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '\'' || c == '"') {
+            format!("'{}'", arg.replace('\'', r"'\''"))
+        } else {
+            arg.clone()
+        }
+    });
+
+    Ok(std::iter::once(kitten.to_string())
+        .chain(quoted)
+        .collect::<Vec<_>>()
+        .join(" "))
 }
 
 pub struct LaunchCommand {
@@ -217,8 +346,8 @@ impl LaunchCommand {
         self
     }
 
-    pub fn env(mut self, value: Map<String, serde_json::Value>) -> Self {
-        self.env = Some(value);
+    pub fn env(mut self, value: impl Into<Map<String, serde_json::Value>>) -> Self {
+        self.env = Some(value.into());
         self
     }
 
@@ -527,6 +656,34 @@ impl LaunchCommand {
             .payload(serde_json::Value::Object(payload))
             .build())
     }
+
+    /// The id of the window kitty created, e.g. to send text to it next.
+    pub fn parse_response(response: &KittyResponse) -> Result<WindowId, CommandError> {
+        parse_window_id("launch", response)
+    }
+}
+
+/// The new window id in the reply to a window-creating command `cmd`,
+/// which kitty sends as a number or a numeric string. A rejected command
+/// becomes [`CommandError::KittyError`].
+pub(crate) fn parse_window_id(
+    cmd: &str,
+    response: &KittyResponse,
+) -> Result<WindowId, CommandError> {
+    if !response.ok {
+        return Err(CommandError::KittyError(
+            cmd.to_string(),
+            response.error.clone().unwrap_or_default(),
+        ));
+    }
+
+    let id = match &response.data {
+        Some(serde_json::Value::String(s)) => s.trim().parse().ok(),
+        Some(value) => value.as_u64(),
+        None => None,
+    };
+    id.map(WindowId)
+        .ok_or_else(|| CommandError::ExecutionFailed(format!("{}: no window id in response", cmd)))
 }
 
 pub struct EnvCommand {
@@ -534,8 +691,18 @@ pub struct EnvCommand {
 }
 
 impl EnvCommand {
-    pub fn new(env: Map<String, serde_json::Value>) -> Self {
-        Self { env }
+    pub fn new(env: impl Into<Map<String, serde_json::Value>>) -> Self {
+        Self { env: env.into() }
+    }
+
+    /// Remove `names` from the environment of windows launched later.
+    /// kitty takes a `null` value as unset; `VAR=` would set it to an
+    /// empty string instead.
+    pub fn unset(mut self, names: Vec<String>) -> Self {
+        for name in names {
+            self.env.insert(name, serde_json::Value::Null);
+        }
+        self
     }
 
     pub fn build(self) -> Result<KittyMessage, CommandError> {
@@ -559,6 +726,7 @@ impl EnvCommand {
 pub struct SetUserVarsCommand {
     var: Vec<String>,
     match_spec: Option<String>,
+    all: bool,
 }
 
 impl SetUserVarsCommand {
@@ -566,11 +734,20 @@ impl SetUserVarsCommand {
         Self {
             var,
             match_spec: None,
+            all: false,
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
+        self
+    }
+
+    /// Set the vars on every window. `set-user-vars` has no `all` flag of
+    /// its own, so this sends kitty's special `all` match; it cannot be
+    /// combined with [`match_spec`](Self::match_spec).
+    pub fn all(mut self, value: bool) -> Self {
+        self.all = value;
         self
     }
 
@@ -584,12 +761,25 @@ impl SetUserVarsCommand {
             ));
         }
 
+        if self.all && self.match_spec.is_some() {
+            return Err(CommandError::ValidationError(
+                "set-user-vars: all cannot be combined with match".to_string(),
+            ));
+        }
+
         payload.insert("var".to_string(), serde_json::json!(self.var));
 
         if let Some(match_spec) = self.match_spec {
             payload.insert("match".to_string(), serde_json::Value::String(match_spec));
         }
 
+        if self.all {
+            payload.insert(
+                "match".to_string(),
+                serde_json::Value::String("all".to_string()),
+            );
+        }
+
         Ok(CommandBuilder::new("set-user-vars")
             .payload(serde_json::Value::Object(payload))
             .build())
@@ -673,8 +863,8 @@ impl ResizeOSWindowCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -767,13 +957,13 @@ impl DisableLigaturesCommand {
         self
     }
 
-    pub fn match_window(mut self, spec: impl Into<String>) -> Self {
-        self.match_window = Some(spec.into());
+    pub fn match_window(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_window = Some(spec.into().into());
         self
     }
 
-    pub fn match_tab(mut self, spec: impl Into<String>) -> Self {
-        self.match_tab = Some(spec.into());
+    pub fn match_tab(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_tab = Some(spec.into().into());
         self
     }
 
@@ -813,6 +1003,48 @@ impl DisableLigaturesCommand {
     }
 }
 
+/// Signal numbers by name, without the `SIG` prefix. Most are the same
+/// on every Unix; the rest differ between Linux and macOS and are picked
+/// by the target this crate is built for.
+const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", 1),
+    ("INT", 2),
+    ("QUIT", 3),
+    ("ILL", 4),
+    ("TRAP", 5),
+    ("ABRT", 6),
+    ("KILL", 9),
+    ("SEGV", 11),
+    ("PIPE", 13),
+    ("ALRM", 14),
+    ("TERM", 15),
+    ("WINCH", 28),
+    #[cfg(not(target_os = "macos"))]
+    ("USR1", 10),
+    #[cfg(not(target_os = "macos"))]
+    ("USR2", 12),
+    #[cfg(not(target_os = "macos"))]
+    ("CHLD", 17),
+    #[cfg(not(target_os = "macos"))]
+    ("CONT", 18),
+    #[cfg(not(target_os = "macos"))]
+    ("STOP", 19),
+    #[cfg(not(target_os = "macos"))]
+    ("TSTP", 20),
+    #[cfg(target_os = "macos")]
+    ("USR1", 30),
+    #[cfg(target_os = "macos")]
+    ("USR2", 31),
+    #[cfg(target_os = "macos")]
+    ("CHLD", 20),
+    #[cfg(target_os = "macos")]
+    ("CONT", 19),
+    #[cfg(target_os = "macos")]
+    ("STOP", 17),
+    #[cfg(target_os = "macos")]
+    ("TSTP", 18),
+];
+
 pub struct SignalChildCommand {
     signals: Vec<i32>,
     match_spec: Option<String>,
@@ -826,8 +1058,33 @@ impl SignalChildCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    /// Build from signal names such as `"SIGTERM"`. The numbers come from
+    /// the platform this crate was built for, not from kitty's host, so
+    /// they only match kitty when both run on the same OS. The `SIG`
+    /// prefix is optional and case is ignored.
+    pub fn from_names(names: Vec<&str>) -> Result<Self, CommandError> {
+        let signals = names
+            .into_iter()
+            .map(|name| {
+                let upper = name.to_ascii_uppercase();
+                let bare = upper.strip_prefix("SIG").unwrap_or(&upper);
+                SIGNALS
+                    .iter()
+                    .find(|(known, _)| *known == bare)
+                    .map(|(_, number)| *number)
+                    .ok_or_else(|| {
+                        CommandError::InvalidParameter(
+                            "signals".to_string(),
+                            format!("unknown signal name '{}'", name),
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(signals))
+    }
+
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -893,6 +1150,61 @@ mod tests {
         assert_eq!(msg.cmd, "kitten");
     }
 
+    #[test]
+    fn test_kitten_hints() {
+        let msg = KittenCommand::hints(["--type", "url"]).build().unwrap();
+        assert_eq!(msg.payload.unwrap()["args"], "hints --type url");
+    }
+
+    #[test]
+    fn test_kitten_unicode_input() {
+        let msg = KittenCommand::unicode_input().build().unwrap();
+        assert_eq!(msg.payload.unwrap()["args"], "unicode_input");
+    }
+
+    #[test]
+    fn test_kitten_themes() {
+        let msg = KittenCommand::themes("Tokyo Night").build().unwrap();
+        assert_eq!(msg.payload.unwrap()["args"], "themes 'Tokyo Night'");
+
+        let msg = KittenCommand::themes("Rosé Pine's Dawn").build().unwrap();
+        assert_eq!(
+            msg.payload.unwrap()["args"],
+            r"themes 'Rosé Pine'\''s Dawn'"
+        );
+
+        assert!(matches!(
+            KittenCommand::themes(" ").build(),
+            Err(CommandError::MissingParameter(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_kitten_ssh() {
+        let msg = KittenCommand::ssh("me@example.com")
+            .match_spec("id:1")
+            .build()
+            .unwrap();
+        let payload = msg.payload.unwrap();
+        assert_eq!(payload["args"], "ssh me@example.com");
+        assert_eq!(payload["match"], "id:1");
+
+        assert!(matches!(
+            KittenCommand::ssh("").build(),
+            Err(CommandError::MissingParameter(_, _))
+        ));
+        assert!(matches!(
+            KittenCommand::ssh("-oProxyCommand=x").build(),
+            Err(CommandError::InvalidParameter(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_kitten_clipboard() {
+        let msg = KittenCommand::clipboard().build().unwrap();
+        assert_eq!(msg.payload.unwrap()["args"], "clipboard");
+    }
+
     #[test]
     fn test_launch_basic() {
         let cmd = LaunchCommand::new().build();
@@ -914,6 +1226,29 @@ mod tests {
         assert_eq!(msg.cmd, "launch");
     }
 
+    #[test]
+    fn test_launch_parse_response() {
+        let response = |body: &str| -> KittyResponse { serde_json::from_str(body).unwrap() };
+
+        let id = LaunchCommand::parse_response(&response(r#"{"ok":true,"data":"42"}"#));
+        assert_eq!(id.unwrap(), WindowId(42));
+        let id = LaunchCommand::parse_response(&response(r#"{"ok":true,"data":7}"#));
+        assert_eq!(id.unwrap(), WindowId(7));
+
+        match LaunchCommand::parse_response(&response(r#"{"ok":false,"error":"bad cwd"}"#)) {
+            Err(CommandError::KittyError(cmd, error)) => {
+                assert_eq!(cmd, "launch");
+                assert_eq!(error, "bad cwd");
+            }
+            other => panic!("Expected KittyError, got {:?}", other),
+        }
+
+        assert!(matches!(
+            LaunchCommand::parse_response(&response(r#"{"ok":true}"#)),
+            Err(CommandError::ExecutionFailed(_))
+        ));
+    }
+
     #[test]
     fn test_env_basic() {
         let mut env_map = Map::new();
@@ -939,6 +1274,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_env_unset() {
+        let env = EnvMap::new().set("EDITOR", "vim");
+        let msg = EnvCommand::new(env)
+            .unset(vec!["PAGER".to_string()])
+            .build()
+            .unwrap();
+        let payload = msg.payload.unwrap();
+        assert_eq!(payload["env"]["EDITOR"], "vim");
+        assert!(payload["env"]["PAGER"].is_null());
+
+        let msg = EnvCommand::new(Map::new())
+            .unset(vec!["PAGER".to_string()])
+            .build()
+            .unwrap();
+        assert_eq!(
+            msg.payload.unwrap()["env"],
+            serde_json::json!({"PAGER": null})
+        );
+
+        let cmd = EnvCommand::new(Map::new()).unset(Vec::new()).build();
+        assert!(matches!(cmd, Err(CommandError::MissingParameter(_, _))));
+    }
+
+    #[test]
+    fn test_env_map() {
+        let env = EnvMap::new()
+            .set("EDITOR", "vim")
+            .set("SHLVL", 2.to_string());
+        let map: Map<String, serde_json::Value> = env.clone().into();
+        assert_eq!(map["EDITOR"], "vim");
+        assert_eq!(map["SHLVL"], "2");
+
+        let msg = EnvCommand::new(env.clone()).build().unwrap();
+        assert_eq!(msg.payload.unwrap()["env"]["SHLVL"], "2");
+
+        let msg = RunCommand::new().env(env.clone()).build().unwrap();
+        assert_eq!(msg.payload.unwrap()["env"]["EDITOR"], "vim");
+
+        let msg = LaunchCommand::new().env(env).build().unwrap();
+        assert_eq!(msg.payload.unwrap()["env"]["EDITOR"], "vim");
+    }
+
+    #[test]
+    fn test_env_map_rejects_non_strings() {
+        let mut map = Map::new();
+        map.insert("EDITOR".to_string(), serde_json::json!("vim"));
+        assert!(EnvMap::try_from(map.clone()).is_ok());
+
+        map.insert("SHLVL".to_string(), serde_json::json!(2));
+        assert!(matches!(
+            EnvMap::try_from(map),
+            Err(CommandError::InvalidParameter(_, _))
+        ));
+    }
+
     #[test]
     fn test_set_user_vars_basic() {
         let cmd = SetUserVarsCommand::new(vec!["var1".to_string(), "var2".to_string()]).build();
@@ -947,6 +1338,23 @@ mod tests {
         assert_eq!(msg.cmd, "set-user-vars");
     }
 
+    #[test]
+    fn test_set_user_vars_all() {
+        let msg = SetUserVarsCommand::new(vec!["theme=dark".to_string()])
+            .all(true)
+            .build()
+            .unwrap();
+        let payload = msg.payload.unwrap();
+        assert_eq!(payload["match"], "all");
+        assert_eq!(payload["var"], serde_json::json!(["theme=dark"]));
+
+        let cmd = SetUserVarsCommand::new(vec!["theme=dark".to_string()])
+            .all(true)
+            .match_spec("id:1")
+            .build();
+        assert!(matches!(cmd, Err(CommandError::ValidationError(_))));
+    }
+
     #[test]
     fn test_set_user_vars_empty() {
         let cmd = SetUserVarsCommand::new(vec![]).build();
@@ -1026,6 +1434,24 @@ mod tests {
         assert_eq!(msg.cmd, "signal-child");
     }
 
+    #[test]
+    fn test_signal_child_from_names() {
+        let msg = SignalChildCommand::from_names(vec!["SIGTERM", "kill", "SigInt"])
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            msg.payload.unwrap()["signals"],
+            serde_json::json!([15, 9, 2])
+        );
+
+        let err = SignalChildCommand::from_names(vec!["SIGTERM", "SIGBOGUS"]);
+        assert!(matches!(
+            err,
+            Err(CommandError::InvalidParameter(field, _)) if field == "signals"
+        ));
+    }
+
     #[test]
     fn test_signal_child_empty() {
         let cmd = SignalChildCommand::new(vec![]).build();
@@ -1,7 +1,32 @@
+use crate::color::Color;
 use crate::command::CommandBuilder;
+use crate::commands::config::debug_config_options;
+use crate::commands::window::read_png_data;
 use crate::error::CommandError;
-use crate::protocol::KittyMessage;
-use serde_json::Map;
+use crate::match_spec::MatchSpec;
+use crate::protocol::{KittyMessage, KittyResponse};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+/// kitty's CLI treats `--all` and `--match`/`--match-tab` as mutually
+/// exclusive: `all` would silently override the match.
+fn validate_all_without_match(
+    cmd: &str,
+    all: bool,
+    match_window: &Option<String>,
+    match_tab: &Option<String>,
+) -> Result<(), CommandError> {
+    if all && (match_window.is_some() || match_tab.is_some()) {
+        return Err(CommandError::ValidationError(format!(
+            "{}: all cannot be combined with match_window or match_tab",
+            cmd
+        )));
+    }
+    Ok(())
+}
 
 pub struct SetBackgroundOpacityCommand {
     opacity: f32,
@@ -22,13 +47,13 @@ impl SetBackgroundOpacityCommand {
         }
     }
 
-    pub fn match_window(mut self, spec: impl Into<String>) -> Self {
-        self.match_window = Some(spec.into());
+    pub fn match_window(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_window = Some(spec.into().into());
         self
     }
 
-    pub fn match_tab(mut self, spec: impl Into<String>) -> Self {
-        self.match_tab = Some(spec.into());
+    pub fn match_tab(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_tab = Some(spec.into().into());
         self
     }
 
@@ -51,6 +76,13 @@ impl SetBackgroundOpacityCommand {
             ));
         }
 
+        validate_all_without_match(
+            "set-background-opacity",
+            self.all,
+            &self.match_window,
+            &self.match_tab,
+        )?;
+
         payload.insert("opacity".to_string(), serde_json::json!(self.opacity));
 
         if let Some(match_window) = self.match_window {
@@ -100,8 +132,14 @@ impl SetBackgroundImageCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    /// Use the PNG at `path` as the background image. Images over the
+    /// 4 KiB chunk size are streamed by `Kitty::execute_all`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(read_png_data(path)?))
+    }
+
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -175,13 +213,13 @@ impl SetColorsCommand {
         }
     }
 
-    pub fn match_window(mut self, spec: impl Into<String>) -> Self {
-        self.match_window = Some(spec.into());
+    pub fn match_window(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_window = Some(spec.into().into());
         self
     }
 
-    pub fn match_tab(mut self, spec: impl Into<String>) -> Self {
-        self.match_tab = Some(spec.into());
+    pub fn match_tab(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_tab = Some(spec.into().into());
         self
     }
 
@@ -210,7 +248,16 @@ impl SetColorsCommand {
             ));
         }
 
-        payload.insert("colors".to_string(), serde_json::Value::Object(self.colors));
+        validate_all_without_match("set-colors", self.all, &self.match_window, &self.match_tab)?;
+
+        let mut colors = self.colors;
+        for (key, value) in colors.iter_mut() {
+            if let Value::String(color) = value {
+                *color = normalize_color(key, color)?;
+            }
+        }
+
+        payload.insert("colors".to_string(), serde_json::Value::Object(colors));
 
         if let Some(match_window) = self.match_window {
             payload.insert(
@@ -244,6 +291,28 @@ impl SetColorsCommand {
     }
 }
 
+/// Hex colors (`#rrggbb`, `#rgb`, or six/three hex digits missing the `#`)
+/// are checked and written as `#rrggbb`. Anything else, such as color
+/// names or `none`, is left for kitty to interpret.
+fn normalize_color(key: &str, value: &str) -> Result<String, CommandError> {
+    let looks_hex = value.starts_with('#')
+        || (matches!(value.len(), 3 | 6) && value.chars().all(|c| c.is_ascii_hexdigit()));
+    if !looks_hex {
+        return Ok(value.to_string());
+    }
+
+    Color::from_hex(value)
+        .map(|color| color.to_string())
+        .ok_or_else(|| {
+            CommandError::InvalidParameter(
+                key.to_string(),
+                format!("'{}' is not a valid hex color", value),
+            )
+        })
+}
+
+/// Change the font size. kitty's `set-font-size` has no match fields: it
+/// acts on the active OS window, or on every OS window with `all`.
 pub struct SetFontSizeCommand {
     size: i32,
     all: bool,
@@ -310,13 +379,13 @@ impl SetSpacingCommand {
         }
     }
 
-    pub fn match_window(mut self, spec: impl Into<String>) -> Self {
-        self.match_window = Some(spec.into());
+    pub fn match_window(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_window = Some(spec.into().into());
         self
     }
 
-    pub fn match_tab(mut self, spec: impl Into<String>) -> Self {
-        self.match_tab = Some(spec.into());
+    pub fn match_tab(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_tab = Some(spec.into().into());
         self
     }
 
@@ -340,6 +409,8 @@ impl SetSpacingCommand {
             ));
         }
 
+        validate_all_without_match("set-spacing", self.all, &self.match_window, &self.match_tab)?;
+
         payload.insert(
             "settings".to_string(),
             serde_json::Value::Object(self.settings),
@@ -388,8 +459,8 @@ impl SetTabColorCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -437,8 +508,8 @@ impl GetColorsCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -462,6 +533,93 @@ impl GetColorsCommand {
             .payload(serde_json::Value::Object(payload))
             .build())
     }
+
+    /// The colors in a `get-colors` response, by name (`foreground`,
+    /// `background`, `color0`..`color255`, ...).
+    ///
+    /// kitty replies with text lines of `<name> <value>`; a JSON object,
+    /// sent directly or encoded as a string, is accepted as well.
+    pub fn parse_response(
+        response: &KittyResponse,
+    ) -> Result<HashMap<String, String>, serde_json::Error> {
+        match &response.data {
+            Some(Value::String(s)) if s.trim_start().starts_with('{') => serde_json::from_str(s),
+            Some(Value::String(s)) => Ok(s
+                .lines()
+                .filter_map(|line| line.trim().split_once(char::is_whitespace))
+                .map(|(name, value)| (name.to_string(), value.trim().to_string()))
+                .collect()),
+            Some(data) => HashMap::deserialize(data),
+            None => Ok(HashMap::new()),
+        }
+    }
+}
+
+/// kitty's default `font_family`, reported when the config leaves it unset.
+pub const DEFAULT_FONT_FAMILY: &str = "monospace";
+
+/// kitty's default `font_size`, in points.
+pub const DEFAULT_FONT_SIZE: f64 = 11.0;
+
+/// The configured font, as reported by kitty's `debug_config` output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontInfo {
+    pub family: String,
+    pub size: f64,
+}
+
+impl Default for FontInfo {
+    fn default() -> Self {
+        Self {
+            family: DEFAULT_FONT_FAMILY.to_string(),
+            size: DEFAULT_FONT_SIZE,
+        }
+    }
+}
+
+impl FontInfo {
+    /// Extract the font settings from `debug_config` output.
+    ///
+    /// kitty only lists options that differ from the defaults, so missing
+    /// lines fall back to [`DEFAULT_FONT_FAMILY`] and [`DEFAULT_FONT_SIZE`].
+    /// Both the plain `font_family Name` form and the newer
+    /// `font_family family="Name" ...` form are understood.
+    pub fn from_debug_config(output: &str) -> Self {
+        let mut info = Self::default();
+
+        for (key, value) in debug_config_options(output) {
+            match key.as_str() {
+                "font_family" => info.family = parse_font_family(&value),
+                "font_size" => {
+                    if let Ok(size) = value.parse() {
+                        info.size = size;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        info
+    }
+}
+
+fn parse_font_family(value: &str) -> String {
+    let Some(rest) = value.strip_prefix("family=") else {
+        return value.to_string();
+    };
+
+    match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => rest[1..]
+            .split(quote)
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        _ => rest
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+    }
 }
 
 #[cfg(test)]
@@ -498,6 +656,21 @@ mod tests {
         assert_eq!(msg.cmd, "set-background-opacity");
     }
 
+    #[test]
+    fn test_set_background_opacity_all_with_match() {
+        let cmd = SetBackgroundOpacityCommand::new(0.8)
+            .all(true)
+            .match_window("id:1")
+            .build();
+        assert!(matches!(cmd, Err(CommandError::ValidationError(_))));
+
+        let cmd = SetBackgroundOpacityCommand::new(0.8)
+            .all(true)
+            .match_tab("id:1")
+            .build();
+        assert!(matches!(cmd, Err(CommandError::ValidationError(_))));
+    }
+
     #[test]
     fn test_set_background_image_basic() {
         let cmd = SetBackgroundImageCommand::new("base64data").build();
@@ -529,6 +702,54 @@ mod tests {
         assert_eq!(msg.cmd, "set-background-image");
     }
 
+    #[test]
+    fn test_set_background_image_from_file() {
+        let path =
+            std::env::temp_dir().join(format!("kitty-rc-background-{}.png", std::process::id()));
+        std::fs::write(&path, b"\x89PNG\r\n\x1a\nrest").unwrap();
+        let msg = SetBackgroundImageCommand::from_file(&path)
+            .unwrap()
+            .all(true)
+            .build()
+            .unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let payload = msg.payload.unwrap();
+        assert_eq!(payload["data"], "iVBORw0KGgpyZXN0");
+        assert_eq!(payload["all"], true);
+
+        let missing = std::env::temp_dir().join("kitty-rc-missing.png");
+        assert!(SetBackgroundImageCommand::from_file(missing).is_err());
+    }
+
+    #[test]
+    fn test_set_background_image_streamed_chunks() {
+        let path = std::env::temp_dir().join(format!(
+            "kitty-rc-background-large-{}.png",
+            std::process::id()
+        ));
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        bytes.extend(std::iter::repeat_n(1u8, 6000));
+        std::fs::write(&path, &bytes).unwrap();
+        let msg = SetBackgroundImageCommand::from_file(&path)
+            .unwrap()
+            .match_spec("id:2")
+            .layout("tiled")
+            .build()
+            .unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let chunks = msg.into_chunks();
+        assert!(chunks.len() > 2);
+        for chunk in &chunks {
+            let payload = chunk.payload.as_ref().unwrap();
+            assert_eq!(payload["match"], "id:2");
+            assert_eq!(payload["layout"], "tiled");
+            assert_eq!(chunk.stream, Some(true));
+        }
+        assert_eq!(chunks.last().unwrap().payload.as_ref().unwrap()["data"], "");
+    }
+
     #[test]
     fn test_set_colors_basic() {
         let mut colors = Map::new();
@@ -567,6 +788,55 @@ mod tests {
         assert_eq!(msg.cmd, "set-colors");
     }
 
+    #[test]
+    fn test_set_colors_normalizes_hex() {
+        let mut colors = Map::new();
+        colors.insert("foreground".to_string(), serde_json::json!("FFFFFF"));
+        colors.insert("background".to_string(), serde_json::json!("#0a0"));
+        colors.insert("cursor".to_string(), serde_json::json!("none"));
+        colors.insert("color1".to_string(), serde_json::json!("red"));
+
+        let msg = SetColorsCommand::new(colors).build().unwrap();
+        let colors = &msg.payload.unwrap()["colors"];
+        assert_eq!(colors["foreground"], "#ffffff");
+        assert_eq!(colors["background"], "#00aa00");
+        assert_eq!(colors["cursor"], "none");
+        assert_eq!(colors["color1"], "red");
+    }
+
+    #[test]
+    fn test_set_colors_rejects_bad_hex() {
+        let mut colors = Map::new();
+        colors.insert("foreground".to_string(), serde_json::json!("#ffffff"));
+        colors.insert("background".to_string(), serde_json::json!("#xyz"));
+
+        match SetColorsCommand::new(colors).build() {
+            Err(CommandError::InvalidParameter(key, msg)) => {
+                assert_eq!(key, "background");
+                assert!(msg.contains("#xyz"));
+            }
+            _ => panic!("Expected InvalidParameter error"),
+        }
+    }
+
+    #[test]
+    fn test_set_colors_all_with_match() {
+        let mut colors = Map::new();
+        colors.insert("background".to_string(), serde_json::json!("#000000"));
+
+        let cmd = SetColorsCommand::new(colors.clone())
+            .all(true)
+            .match_window("id:1")
+            .build();
+        assert!(matches!(cmd, Err(CommandError::ValidationError(_))));
+
+        let cmd = SetColorsCommand::new(colors)
+            .all(true)
+            .match_tab("title:logs")
+            .build();
+        assert!(matches!(cmd, Err(CommandError::ValidationError(_))));
+    }
+
     #[test]
     fn test_set_font_size_basic() {
         let cmd = SetFontSizeCommand::new(14).build();
@@ -621,6 +891,24 @@ mod tests {
         assert_eq!(msg.cmd, "set-spacing");
     }
 
+    #[test]
+    fn test_set_spacing_all_with_match() {
+        let mut settings = Map::new();
+        settings.insert("margin".to_string(), serde_json::json!(5));
+
+        let cmd = SetSpacingCommand::new(settings.clone())
+            .all(true)
+            .match_window("id:1")
+            .build();
+        assert!(matches!(cmd, Err(CommandError::ValidationError(_))));
+
+        let cmd = SetSpacingCommand::new(settings)
+            .all(true)
+            .match_tab("id:2")
+            .build();
+        assert!(matches!(cmd, Err(CommandError::ValidationError(_))));
+    }
+
     #[test]
     fn test_set_tab_color_basic() {
         let mut colors = Map::new();
@@ -677,4 +965,69 @@ mod tests {
         let msg = cmd.unwrap();
         assert_eq!(msg.cmd, "get-colors");
     }
+
+    #[test]
+    fn test_get_colors_parse_response() {
+        let response = |data: Option<Value>| KittyResponse {
+            ok: true,
+            data,
+            error: None,
+        };
+
+        let text = response(Some(Value::String(
+            "active_border_color     #00ff00\nbackground              #000000\ncolor0                  #1d1f21\ncursor                  none\n"
+                .to_string(),
+        )));
+        let colors = GetColorsCommand::parse_response(&text).unwrap();
+        assert_eq!(colors.len(), 4);
+        assert_eq!(colors["background"], "#000000");
+        assert_eq!(colors["color0"], "#1d1f21");
+        assert_eq!(colors["cursor"], "none");
+
+        let encoded = response(Some(Value::String(
+            serde_json::json!({"foreground": "#ffffff"}).to_string(),
+        )));
+        let colors = GetColorsCommand::parse_response(&encoded).unwrap();
+        assert_eq!(colors["foreground"], "#ffffff");
+
+        let object = response(Some(serde_json::json!({"color255": "#eeeeee"})));
+        let colors = GetColorsCommand::parse_response(&object).unwrap();
+        assert_eq!(colors["color255"], "#eeeeee");
+
+        assert!(
+            GetColorsCommand::parse_response(&response(None))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(GetColorsCommand::parse_response(&response(Some(serde_json::json!(1)))).is_err());
+    }
+
+    const DEBUG_CONFIG_SAMPLE: &str = "kitty 0.36.4 created by Kovid Goyal
+Linux 6.8.0 #1 SMP x86_64
+Running under: Wayland
+
+Loaded config files:
+  /home/user/.config/kitty/kitty.conf
+
+Config options different from defaults:
+font_family               family=\"JetBrains Mono\" postscript_name=JetBrainsMono-Regular
+font_size                 13.5
+background_opacity        0.9
+";
+
+    #[test]
+    fn test_font_info_from_debug_config() {
+        let info = FontInfo::from_debug_config(DEBUG_CONFIG_SAMPLE);
+        assert_eq!(info.family, "JetBrains Mono");
+        assert_eq!(info.size, 13.5);
+    }
+
+    #[test]
+    fn test_font_info_plain_family_and_defaults() {
+        let info = FontInfo::from_debug_config("font_family   Fira Code\n");
+        assert_eq!(info.family, "Fira Code");
+        assert_eq!(info.size, DEFAULT_FONT_SIZE);
+
+        assert_eq!(FontInfo::from_debug_config(""), FontInfo::default());
+    }
 }
@@ -1,6 +1,10 @@
 use crate::command::CommandBuilder;
+use crate::commands::layout::GotoLayoutCommand;
+use crate::commands::process::parse_window_id;
 use crate::error::CommandError;
-use crate::protocol::KittyMessage;
+use crate::ids::WindowId;
+use crate::match_spec::MatchSpec;
+use crate::protocol::{KittyMessage, KittyResponse};
 
 pub struct FocusTabCommand {
     match_spec: Option<String>,
@@ -11,8 +15,8 @@ impl FocusTabCommand {
         Self { match_spec: None }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -42,8 +46,8 @@ impl SetTabTitleCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -84,8 +88,8 @@ impl CloseTabCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -135,8 +139,8 @@ impl DetachTabCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -174,6 +178,106 @@ impl DetachTabCommand {
     }
 }
 
+/// Open a new tab via kitty's `launch --type=tab`.
+///
+/// kitty's `launch` has no layout option, so [`layout`](Self::layout) is
+/// applied by sending [`layout_command`](Self::layout_command) once the
+/// tab exists.
+#[derive(Default)]
+pub struct NewTabCommand {
+    args: Option<String>,
+    tab_title: Option<String>,
+    cwd: Option<String>,
+    layout: Option<String>,
+    keep_focus: bool,
+}
+
+impl NewTabCommand {
+    pub fn new() -> Self {
+        Self {
+            args: None,
+            tab_title: None,
+            cwd: None,
+            layout: None,
+            keep_focus: false,
+        }
+    }
+
+    pub fn args(mut self, value: impl Into<String>) -> Self {
+        self.args = Some(value.into());
+        self
+    }
+
+    pub fn tab_title(mut self, value: impl Into<String>) -> Self {
+        self.tab_title = Some(value.into());
+        self
+    }
+
+    pub fn cwd(mut self, value: impl Into<String>) -> Self {
+        self.cwd = Some(value.into());
+        self
+    }
+
+    pub fn layout(mut self, value: impl Into<String>) -> Self {
+        self.layout = Some(value.into());
+        self
+    }
+
+    pub fn keep_focus(mut self, value: bool) -> Self {
+        self.keep_focus = value;
+        self
+    }
+
+    /// The follow-up `goto-layout` for the tab holding `window_id`, the id
+    /// returned by [`parse_response`](Self::parse_response). `None` when no
+    /// layout was requested.
+    pub fn layout_command(&self, window_id: impl Into<WindowId>) -> Option<GotoLayoutCommand> {
+        let window_id = window_id.into();
+        self.layout.as_ref().map(|layout| {
+            GotoLayoutCommand::new(layout.clone()).match_spec(format!("window_id:{}", window_id))
+        })
+    }
+
+    pub fn build(self) -> Result<KittyMessage, CommandError> {
+        let mut payload = serde_json::Map::new();
+
+        payload.insert(
+            "window_type".to_string(),
+            serde_json::Value::String("tab".to_string()),
+        );
+
+        if let Some(args) = self.args {
+            payload.insert("args".to_string(), serde_json::Value::String(args));
+        }
+
+        if let Some(tab_title) = self.tab_title {
+            payload.insert(
+                "tab_title".to_string(),
+                serde_json::Value::String(tab_title),
+            );
+        }
+
+        if let Some(cwd) = self.cwd {
+            payload.insert("cwd".to_string(), serde_json::Value::String(cwd));
+        }
+
+        if self.keep_focus {
+            payload.insert("keep_focus".to_string(), serde_json::Value::Bool(true));
+        }
+
+        Ok(CommandBuilder::new("launch")
+            .payload(serde_json::Value::Object(payload))
+            .build())
+    }
+
+    /// The id kitty reports for the launch: the window created in the new
+    /// tab. Target the tab itself with the `window_id:<id>` tab match. A
+    /// rejected launch becomes [`CommandError::KittyError`].
+    pub fn parse_response(response: &KittyResponse) -> Result<WindowId, CommandError> {
+        parse_window_id("launch", response)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -301,4 +405,75 @@ mod tests {
         let msg = cmd.unwrap();
         assert_eq!(msg.cmd, "detach-tab");
     }
+
+    #[test]
+    fn test_new_tab_basic() {
+        let msg = NewTabCommand::new().build().unwrap();
+        assert_eq!(msg.cmd, "launch");
+        assert_eq!(msg.payload.unwrap()["window_type"], "tab");
+    }
+
+    #[test]
+    fn test_new_tab_with_options() {
+        let cmd = NewTabCommand::new()
+            .args("htop")
+            .tab_title("Monitor")
+            .cwd("/tmp")
+            .layout("stack")
+            .keep_focus(true);
+        assert!(cmd.layout_command(7).is_some());
+
+        let msg = cmd.build().unwrap();
+        assert_eq!(msg.cmd, "launch");
+        let payload = msg.payload.unwrap();
+        assert_eq!(payload["tab_title"], "Monitor");
+        assert_eq!(payload["cwd"], "/tmp");
+        assert_eq!(payload["keep_focus"], true);
+        assert!(payload.get("layout").is_none());
+    }
+
+    #[test]
+    fn test_new_tab_layout_command() {
+        assert!(NewTabCommand::new().layout_command(7).is_none());
+
+        let msg = NewTabCommand::new()
+            .layout("tall")
+            .layout_command(7)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(msg.cmd, "goto-layout");
+        assert_eq!(msg.payload.unwrap()["match"], "window_id:7");
+    }
+
+    #[test]
+    fn test_new_tab_parse_response() {
+        let response = |data| KittyResponse {
+            ok: true,
+            data,
+            error: None,
+        };
+        assert_eq!(
+            NewTabCommand::parse_response(&response(Some(serde_json::json!("12")))).unwrap(),
+            WindowId(12)
+        );
+        assert_eq!(
+            NewTabCommand::parse_response(&response(Some(serde_json::json!(3)))).unwrap(),
+            WindowId(3)
+        );
+        assert!(matches!(
+            NewTabCommand::parse_response(&response(None)),
+            Err(CommandError::ExecutionFailed(_))
+        ));
+
+        let rejected = KittyResponse {
+            ok: false,
+            data: None,
+            error: Some("No such layout".to_string()),
+        };
+        assert!(matches!(
+            NewTabCommand::parse_response(&rejected),
+            Err(CommandError::KittyError(cmd, msg)) if cmd == "launch" && msg == "No such layout"
+        ));
+    }
 }
@@ -1,14 +1,20 @@
 use crate::command::CommandBuilder;
-use crate::commands::process::ProcessInfo;
-use crate::error::CommandError;
+use crate::commands::process::{ProcessInfo, parse_window_id};
+use crate::error::{CommandError, ProtocolError};
+use crate::ids::{OsWindowId, TabId, WindowId};
+use crate::match_spec::MatchSpec;
 use crate::protocol::KittyMessage;
-use serde::Deserialize;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct WindowInfo {
-    pub id: Option<u64>,
+    pub id: Option<WindowId>,
     pub title: Option<String>,
     pub pid: Option<u64>,
     pub cwd: Option<String>,
@@ -22,7 +28,9 @@ pub struct WindowInfo {
     #[serde(default)]
     pub env: HashMap<String, String>,
     pub in_alternate_screen: Option<bool>,
+    /// The active window of its tab. Every tab has one, focused or not.
     pub is_active: Option<bool>,
+    /// The one window that has keyboard focus, across all OS windows.
     pub is_focused: Option<bool>,
     pub is_self: Option<bool>,
     pub last_cmd_exit_status: Option<i32>,
@@ -32,7 +40,27 @@ pub struct WindowInfo {
     pub user_vars: HashMap<String, String>,
 }
 
-#[derive(Debug, Deserialize)]
+impl WindowInfo {
+    /// The command the window was launched with.
+    ///
+    /// kitty does not keep the original `launch` arguments; `cmdline` is
+    /// the argv of the window's child process, which is the launched
+    /// command unless it re-executed itself. When kitty omits it, fall back
+    /// to the foreground process with the window's `pid`.
+    pub fn launch_cmdline(&self) -> Option<&[String]> {
+        if !self.cmdline.is_empty() {
+            return Some(&self.cmdline);
+        }
+
+        self.foreground_processes
+            .iter()
+            .find(|process| process.pid.is_some() && process.pid == self.pid)
+            .map(|process| process.cmdline.as_slice())
+            .filter(|cmdline| !cmdline.is_empty())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct LayoutOpts {
     #[serde(default)]
     pub bias: i32,
@@ -42,14 +70,14 @@ pub struct LayoutOpts {
     pub mirrored: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct WindowGroup {
     pub id: u64,
     #[serde(default)]
     pub window_ids: Vec<u64>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct AllWindows {
     #[serde(default)]
     pub active_group_history: Vec<u64>,
@@ -58,7 +86,7 @@ pub struct AllWindows {
     pub window_groups: Vec<WindowGroup>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct LayoutState {
     pub all_windows: Option<AllWindows>,
     #[serde(default)]
@@ -67,16 +95,50 @@ pub struct LayoutState {
     #[serde(default)]
     pub main_bias: Vec<f32>,
     pub opts: Option<LayoutOpts>,
+    /// Root of the split tree, only reported by the `splits` layout.
+    pub pairs: Option<SplitPair>,
 }
 
-#[derive(Debug, Deserialize)]
+/// One split in the `splits` layout, as serialized by kitty. `one` and
+/// `two` refer to window group ids, see [`AllWindows::window_groups`].
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SplitPair {
+    #[serde(default)]
+    pub horizontal: bool,
+    pub bias: Option<f32>,
+    pub one: Option<SplitChild>,
+    pub two: Option<SplitChild>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum SplitChild {
+    Group(u64),
+    Pair(Box<SplitPair>),
+}
+
+/// A tab's split structure with window groups resolved to window ids, as
+/// built by [`TabInfo::split_tree`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SplitTree {
+    /// A window group: its main window plus any overlays on top of it.
+    Leaf { group: u64, windows: Vec<u64> },
+    Split {
+        horizontal: bool,
+        bias: f32,
+        first: Box<SplitTree>,
+        second: Box<SplitTree>,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct TabGroup {
     pub id: u64,
     #[serde(default)]
     pub windows: Vec<u64>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct TabInfo {
     #[serde(default)]
     pub windows: Vec<WindowInfo>,
@@ -86,7 +148,7 @@ pub struct TabInfo {
     pub enabled_layouts: Vec<String>,
     #[serde(default)]
     pub groups: Vec<TabGroup>,
-    pub id: Option<u64>,
+    pub id: Option<TabId>,
     pub is_active: Option<bool>,
     pub is_focused: Option<bool>,
     pub layout: Option<String>,
@@ -95,12 +157,12 @@ pub struct TabInfo {
     pub title: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct OsInstance {
     #[serde(default)]
     pub tabs: Vec<TabInfo>,
     pub background_opacity: Option<f32>,
-    pub id: Option<u64>,
+    pub id: Option<OsWindowId>,
     pub is_active: Option<bool>,
     pub is_focused: Option<bool>,
     pub last_focused: Option<bool>,
@@ -109,6 +171,66 @@ pub struct OsInstance {
     pub wm_name: Option<String>,
 }
 
+impl TabInfo {
+    /// The tree of splits in this tab.
+    ///
+    /// kitty does not record which window was spawned from which, so this
+    /// is the closest thing `ls` offers to parentage: a window split off
+    /// another shares its parent [`SplitTree::Split`]. Only the `splits`
+    /// layout reports this structure, every other layout yields `None`.
+    pub fn split_tree(&self) -> Option<SplitTree> {
+        let state = self.layout_state.as_ref()?;
+        let groups = state
+            .all_windows
+            .as_ref()
+            .map(|all| all.window_groups.as_slice())
+            .unwrap_or_default();
+
+        fn resolve(pair: &SplitPair, groups: &[WindowGroup]) -> Option<SplitTree> {
+            let child = |child: &SplitChild| match child {
+                SplitChild::Group(id) => Some(SplitTree::Leaf {
+                    group: *id,
+                    windows: groups
+                        .iter()
+                        .find(|group| group.id == *id)
+                        .map(|group| group.window_ids.clone())
+                        .unwrap_or_default(),
+                }),
+                SplitChild::Pair(pair) => resolve(pair, groups),
+            };
+
+            let first = pair.one.as_ref().and_then(child);
+            let second = pair.two.as_ref().and_then(child);
+
+            match (first, second) {
+                (Some(first), Some(second)) => Some(SplitTree::Split {
+                    horizontal: pair.horizontal,
+                    bias: pair.bias.unwrap_or(0.5),
+                    first: Box::new(first),
+                    second: Box::new(second),
+                }),
+                (Some(only), None) | (None, Some(only)) => Some(only),
+                (None, None) => None,
+            }
+        }
+
+        resolve(state.pairs.as_ref()?, groups)
+    }
+}
+
+impl OsInstance {
+    /// The title kitty shows for this OS window: the title of the active
+    /// window in the active tab, falling back to the tab title.
+    pub fn title(&self) -> Option<&str> {
+        let tab = self.tabs.iter().find(|t| t.is_active == Some(true))?;
+        tab.windows
+            .iter()
+            .find(|w| w.is_active == Some(true))
+            .and_then(|w| w.title.as_deref())
+            .or(tab.title.as_deref())
+    }
+}
+
 pub fn parse_response_data(data: &Value) -> Result<Vec<OsInstance>, serde_json::Error> {
     let parsed_data = if let Some(s) = data.as_str() {
         serde_json::from_str(s)?
@@ -118,6 +240,84 @@ pub fn parse_response_data(data: &Value) -> Result<Vec<OsInstance>, serde_json::
     serde_json::from_value(parsed_data)
 }
 
+/// The globally focused window in a parsed `ls` response, if any.
+pub fn active_window(instances: &[OsInstance]) -> Option<&WindowInfo> {
+    all_windows(instances).find(|window| window.is_focused == Some(true))
+}
+
+/// The window kitty marked with `is_self`, i.e. the one the caller is
+/// running in. Only set when the request carried a `self` match.
+pub fn self_window(instances: &[OsInstance]) -> Option<&WindowInfo> {
+    all_windows(instances).find(|window| window.is_self == Some(true))
+}
+
+/// Every window in a parsed `ls` tree, in OS window, tab, window order.
+pub fn all_windows(instances: &[OsInstance]) -> impl Iterator<Item = &WindowInfo> {
+    instances
+        .iter()
+        .flat_map(|instance| &instance.tabs)
+        .flat_map(|tab| &tab.windows)
+}
+
+/// Like [`all_windows`], but each window comes with the ids of the OS window
+/// and tab that contain it.
+pub fn all_windows_with_ids(
+    instances: &[OsInstance],
+) -> impl Iterator<Item = (Option<OsWindowId>, Option<TabId>, &WindowInfo)> {
+    instances.iter().flat_map(|instance| {
+        instance.tabs.iter().flat_map(move |tab| {
+            tab.windows
+                .iter()
+                .map(move |window| (instance.id, tab.id, window))
+        })
+    })
+}
+
+/// Changes between two `ls` snapshots, as computed by [`diff_windows`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WindowDiff {
+    pub opened: Vec<WindowId>,
+    pub closed: Vec<WindowId>,
+    /// `(before, after)` focused window ids, set only when focus moved.
+    pub focus_changed: Option<(Option<WindowId>, Option<WindowId>)>,
+}
+
+impl WindowDiff {
+    pub fn is_empty(&self) -> bool {
+        self.opened.is_empty() && self.closed.is_empty() && self.focus_changed.is_none()
+    }
+}
+
+fn window_ids(instances: &[OsInstance]) -> Vec<WindowId> {
+    all_windows(instances)
+        .filter_map(|window| window.id)
+        .collect()
+}
+
+/// Compare two `ls` snapshots: which windows appeared, which went away and
+/// whether the globally focused window changed.
+pub fn diff_windows(before: &[OsInstance], after: &[OsInstance]) -> WindowDiff {
+    let before_ids = window_ids(before);
+    let after_ids = window_ids(after);
+    let before_set: HashSet<WindowId> = before_ids.iter().copied().collect();
+    let after_set: HashSet<WindowId> = after_ids.iter().copied().collect();
+
+    let focus_before = active_window(before).and_then(|window| window.id);
+    let focus_after = active_window(after).and_then(|window| window.id);
+
+    WindowDiff {
+        opened: after_ids
+            .into_iter()
+            .filter(|id| !before_set.contains(id))
+            .collect(),
+        closed: before_ids
+            .into_iter()
+            .filter(|id| !after_set.contains(id))
+            .collect(),
+        focus_changed: (focus_before != focus_after).then_some((focus_before, focus_after)),
+    }
+}
+
 use crate::protocol::KittyResponse;
 
 pub struct LsCommand {
@@ -142,13 +342,13 @@ impl LsCommand {
         self
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
-    pub fn match_tab(mut self, spec: impl Into<String>) -> Self {
-        self.match_tab = Some(spec.into());
+    pub fn match_tab(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_tab = Some(spec.into().into());
         self
     }
 
@@ -191,6 +391,117 @@ impl LsCommand {
             Ok(vec![])
         }
     }
+
+    /// Pull just the focused window id out of an `ls` response. Only the
+    /// ids and focus flags are deserialized, so the rest of the tree (env,
+    /// processes, titles) is skipped rather than allocated.
+    pub fn parse_focused_id(
+        response: &KittyResponse,
+    ) -> Result<Option<WindowId>, serde_json::Error> {
+        #[derive(Deserialize)]
+        struct Window {
+            id: Option<WindowId>,
+            is_focused: Option<bool>,
+        }
+
+        #[derive(Deserialize)]
+        struct Tab {
+            #[serde(default)]
+            windows: Vec<Window>,
+        }
+
+        #[derive(Deserialize)]
+        struct Instance {
+            #[serde(default)]
+            tabs: Vec<Tab>,
+        }
+
+        let instances: Vec<Instance> = match &response.data {
+            Some(Value::String(s)) => serde_json::from_str(s)?,
+            Some(data) => Vec::deserialize(data)?,
+            None => return Ok(None),
+        };
+
+        Ok(instances
+            .into_iter()
+            .flat_map(|instance| instance.tabs)
+            .flat_map(|tab| tab.windows)
+            .find(|window| window.is_focused == Some(true))
+            .and_then(|window| window.id))
+    }
+
+    /// Parse the response body (the JSON between the envelope markers)
+    /// straight into the typed tree, without building a `Value` for it
+    /// first. Gives the same result as [`parse_response`](Self::parse_response)
+    /// at a fraction of the allocations, which matters for large sessions.
+    #[cfg(feature = "raw-value")]
+    pub fn parse_response_raw(body: &str) -> Result<Vec<OsInstance>, serde_json::Error> {
+        use serde_json::value::RawValue;
+        use std::borrow::Cow;
+
+        #[derive(Deserialize)]
+        struct RawResponse<'a> {
+            #[serde(borrow)]
+            data: Option<&'a RawValue>,
+        }
+
+        let response: RawResponse = serde_json::from_str(body)?;
+        let Some(data) = response.data else {
+            return Ok(vec![]);
+        };
+
+        // kitty sends the tree as a JSON-encoded string
+        if data.get().starts_with('"') {
+            let data: Cow<str> = serde_json::from_str(data.get())?;
+            serde_json::from_str(&data)
+        } else {
+            serde_json::from_str(data.get())
+        }
+    }
+}
+
+/// Without `all` or a match, kitty targets only the active window, so
+/// excluding it would silently send nothing.
+fn validate_exclude_active(
+    cmd: &str,
+    exclude_active: bool,
+    broad: bool,
+) -> Result<(), CommandError> {
+    if exclude_active && !broad {
+        return Err(CommandError::ValidationError(format!(
+            "{}: exclude_active without all or a match targets no windows",
+            cmd
+        )));
+    }
+    Ok(())
+}
+
+/// How [`SendTextCommand`] rewrites line breaks before sending. Shells
+/// execute on `\r` (Enter), so `Lf` is handy for pasting a block without
+/// running each line, and `Cr` for typing commands line by line.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Cr,
+    CrLf,
+    /// Send the data unchanged.
+    #[default]
+    Keep,
+}
+
+impl LineEnding {
+    /// Rewrite every `\r\n`, `\r` or `\n` in `text` to this line ending.
+    pub fn apply(self, text: &str) -> String {
+        let target = match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Cr => "\r",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Keep => return text.to_string(),
+        };
+        text.replace("\r\n", "\n")
+            .replace('\r', "\n")
+            .replace('\n', target)
+    }
 }
 
 pub struct SendTextCommand {
@@ -200,6 +511,9 @@ pub struct SendTextCommand {
     all: bool,
     exclude_active: bool,
     bracketed_paste: String,
+    line_ending: LineEnding,
+    only_at_prompt: bool,
+    self_window: bool,
 }
 
 impl SendTextCommand {
@@ -211,16 +525,36 @@ impl SendTextCommand {
             all: false,
             exclude_active: false,
             bracketed_paste: "disable".to_string(),
+            line_ending: LineEnding::Keep,
+            only_at_prompt: false,
+            self_window: false,
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    /// Send everything `reader` yields as text, wrapped in kitty's
+    /// `text:` data prefix. Input that is not UTF-8 fails with
+    /// [`io::ErrorKind::InvalidData`]. Content over the 4 KiB chunk size
+    /// goes out as several complete `send-text` commands, see
+    /// [`build_all`](Self::build_all), or let `Kitty::execute_all` split it.
+    pub fn from_reader<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        Ok(Self::new(format!("text:{}", text)))
+    }
+
+    /// Send the contents of the file at `path`, like
+    /// [`from_reader`](Self::from_reader).
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
-    pub fn match_tab(mut self, spec: impl Into<String>) -> Self {
-        self.match_tab = Some(spec.into());
+    pub fn match_tab(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_tab = Some(spec.into().into());
         self
     }
 
@@ -229,16 +563,55 @@ impl SendTextCommand {
         self
     }
 
+    /// Target the window the command is sent from rather than the active
+    /// one, e.g. for a kitten or launched process writing back to its own
+    /// window.
+    pub fn self_window(mut self, value: bool) -> Self {
+        self.self_window = value;
+        self
+    }
+
+    /// Skip the active window, i.e. the active window of the active tab in
+    /// the focused OS window, even when it is matched. Only meaningful
+    /// together with [`all`](Self::all) or a match: on its own the text
+    /// would go to the active window alone, so `build` rejects it.
     pub fn exclude_active(mut self, value: bool) -> Self {
         self.exclude_active = value;
         self
     }
 
+    /// Send to every window except the active one, i.e. `all(true)` plus
+    /// `exclude_active(true)`. kitty skips the active window of the focused
+    /// tab, so the window you are typing in does not receive the text.
+    pub fn broadcast_except_active(self) -> Self {
+        self.all(true).exclude_active(true)
+    }
+
     pub fn bracketed_paste(mut self, value: impl Into<String>) -> Self {
         self.bracketed_paste = value.into();
         self
     }
 
+    /// Normalize line breaks in the data before sending, see [`LineEnding`].
+    pub fn line_ending(mut self, value: LineEnding) -> Self {
+        self.line_ending = value;
+        self
+    }
+
+    /// Refuse to send unless the target window is at a shell prompt, so
+    /// text is never typed into a running program. This is a client-side
+    /// guard: every `Kitty` method that sends the message first runs `ls`
+    /// for the matched window (or the active one) and fails with
+    /// [`CommandError::ValidationError`] when its `at_prompt` is not set,
+    /// costing an extra round trip.
+    /// Needs a single target, so `build` rejects it with
+    /// [`all`](Self::all), [`match_tab`](Self::match_tab) or
+    /// [`self_window`](Self::self_window).
+    pub fn only_at_prompt(mut self, value: bool) -> Self {
+        self.only_at_prompt = value;
+        self
+    }
+
     pub fn build(self) -> Result<KittyMessage, CommandError> {
         let mut payload = serde_json::Map::new();
 
@@ -249,7 +622,23 @@ impl SendTextCommand {
             ));
         }
 
-        payload.insert("data".to_string(), serde_json::Value::String(self.data));
+        validate_exclude_active(
+            "send-text",
+            self.exclude_active,
+            self.all || self.match_spec.is_some() || self.match_tab.is_some() || self.self_window,
+        )?;
+
+        if self.only_at_prompt && (self.all || self.match_tab.is_some() || self.self_window) {
+            return Err(CommandError::ValidationError(
+                "send-text: only_at_prompt needs a window match or the active window, not all, match_tab or self"
+                    .to_string(),
+            ));
+        }
+
+        payload.insert(
+            "data".to_string(),
+            serde_json::Value::String(self.line_ending.apply(&self.data)),
+        );
 
         if let Some(match_spec) = self.match_spec {
             payload.insert("match".to_string(), serde_json::Value::String(match_spec));
@@ -270,6 +659,10 @@ impl SendTextCommand {
             payload.insert("exclude_active".to_string(), serde_json::Value::Bool(true));
         }
 
+        if self.self_window {
+            payload.insert("self".to_string(), serde_json::Value::Bool(true));
+        }
+
         if self.bracketed_paste != "disable" {
             payload.insert(
                 "bracketed_paste".to_string(),
@@ -279,7 +672,16 @@ impl SendTextCommand {
 
         Ok(CommandBuilder::new("send-text")
             .payload(serde_json::Value::Object(payload))
-            .build())
+            .build()
+            .only_at_prompt(self.only_at_prompt))
+    }
+
+    /// Like [`build`](Self::build), but split data over the 4 KiB chunk
+    /// size into several complete `send-text` commands, each with the
+    /// full payload and its own `text:` prefix. Send them in order, each
+    /// answered by kitty.
+    pub fn build_all(self) -> Result<Vec<KittyMessage>, CommandError> {
+        Ok(self.build()?.into_chunks())
     }
 }
 
@@ -289,6 +691,7 @@ pub struct SendKeyCommand {
     match_tab: Option<String>,
     all: bool,
     exclude_active: bool,
+    self_window: bool,
 }
 
 impl SendKeyCommand {
@@ -299,16 +702,17 @@ impl SendKeyCommand {
             match_tab: None,
             all: false,
             exclude_active: false,
+            self_window: false,
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
-    pub fn match_tab(mut self, spec: impl Into<String>) -> Self {
-        self.match_tab = Some(spec.into());
+    pub fn match_tab(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_tab = Some(spec.into().into());
         self
     }
 
@@ -317,11 +721,29 @@ impl SendKeyCommand {
         self
     }
 
+    /// Skip the active window, i.e. the active window of the active tab in
+    /// the focused OS window, even when it is matched. Only meaningful
+    /// together with [`all`](Self::all) or a match: on its own the keys
+    /// would go to the active window alone, so `build` rejects it.
     pub fn exclude_active(mut self, value: bool) -> Self {
         self.exclude_active = value;
         self
     }
 
+    /// Target the window the command is sent from rather than the active
+    /// one.
+    pub fn self_window(mut self, value: bool) -> Self {
+        self.self_window = value;
+        self
+    }
+
+    /// Send to every window except the active one, i.e. `all(true)` plus
+    /// `exclude_active(true)`. kitty skips the active window of the focused
+    /// tab, so the window you are typing in does not receive the keys.
+    pub fn broadcast_except_active(self) -> Self {
+        self.all(true).exclude_active(true)
+    }
+
     pub fn build(self) -> Result<KittyMessage, CommandError> {
         let mut payload = serde_json::Map::new();
 
@@ -332,6 +754,12 @@ impl SendKeyCommand {
             ));
         }
 
+        validate_exclude_active(
+            "send-key",
+            self.exclude_active,
+            self.all || self.match_spec.is_some() || self.match_tab.is_some() || self.self_window,
+        )?;
+
         payload.insert("keys".to_string(), serde_json::Value::String(self.keys));
 
         if let Some(match_spec) = self.match_spec {
@@ -353,6 +781,10 @@ impl SendKeyCommand {
             payload.insert("exclude_active".to_string(), serde_json::Value::Bool(true));
         }
 
+        if self.self_window {
+            payload.insert("self".to_string(), serde_json::Value::Bool(true));
+        }
+
         Ok(CommandBuilder::new("send-key")
             .payload(serde_json::Value::Object(payload))
             .build())
@@ -374,8 +806,8 @@ impl CloseWindowCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -427,8 +859,8 @@ impl ResizeWindowCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -482,8 +914,8 @@ impl FocusWindowCommand {
         Self { match_spec: None }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -517,8 +949,8 @@ impl SelectWindowCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -527,6 +959,8 @@ impl SelectWindowCommand {
         self
     }
 
+    /// Leave the active window (the active window of the active tab in the
+    /// focused OS window) out of the windows offered for selection.
     pub fn exclude_active(mut self, value: bool) -> Self {
         self.exclude_active = value;
         self
@@ -561,7 +995,8 @@ impl SelectWindowCommand {
 
         Ok(CommandBuilder::new("select-window")
             .payload(serde_json::Value::Object(payload))
-            .build())
+            .build()
+            .interactive(true))
     }
 }
 
@@ -664,6 +1099,11 @@ impl NewWindowCommand {
             .payload(serde_json::Value::Object(payload))
             .build())
     }
+
+    /// The id of the window kitty created.
+    pub fn parse_response(response: &KittyResponse) -> Result<WindowId, CommandError> {
+        parse_window_id("new-window", response)
+    }
 }
 
 pub struct DetachWindowCommand {
@@ -683,8 +1123,8 @@ impl DetachWindowCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -746,8 +1186,8 @@ impl SetWindowTitleCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -782,6 +1222,110 @@ impl SetWindowTitleCommand {
     }
 }
 
+/// Set the title shown by an OS window, through the title of its active
+/// window.
+///
+/// kitty has no remote control command that writes an OS window title
+/// directly, and no title an OS window keeps apart from its windows: the OS
+/// window mirrors the title of its active window (unless it was launched
+/// with `os_window_title`, which pins it). This sends `set-window-title` to
+/// the globally focused window by default, so that window's title changes
+/// as well. The title is sent as `temporary`, so the program running in the
+/// window can still set its own title afterwards, unlike with
+/// [`SetWindowTitleCommand`]. Use `match_spec` to target the active window
+/// of another OS window, or `self_window` for the calling window.
+pub struct SetOsWindowTitleCommand {
+    title: String,
+    match_spec: Option<String>,
+    self_window: bool,
+}
+
+impl SetOsWindowTitleCommand {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            match_spec: None,
+            self_window: false,
+        }
+    }
+
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
+        self
+    }
+
+    /// Target the window this process runs in instead of the focused one.
+    /// Tags the message with `KITTY_WINDOW_ID` so kitty knows which window
+    /// that is; `build` fails if the variable is not set, i.e. when not
+    /// running inside kitty.
+    pub fn self_window(mut self, value: bool) -> Self {
+        self.self_window = value;
+        self
+    }
+
+    pub fn build(self) -> Result<KittyMessage, CommandError> {
+        let mut payload = serde_json::Map::new();
+
+        if self.title.is_empty() {
+            return Err(CommandError::MissingParameter(
+                "title".to_string(),
+                "set-window-title".to_string(),
+            ));
+        }
+
+        payload.insert("title".to_string(), serde_json::Value::String(self.title));
+        payload.insert("temporary".to_string(), serde_json::Value::Bool(true));
+
+        // Without a match, set-window-title targets the window named by
+        // kitty_window_id, or the active window when there is none.
+        if !self.self_window {
+            let match_spec = self
+                .match_spec
+                .unwrap_or_else(|| "state:focused".to_string());
+            payload.insert("match".to_string(), serde_json::Value::String(match_spec));
+        }
+
+        let message = CommandBuilder::new("set-window-title")
+            .payload(serde_json::Value::Object(payload))
+            .build();
+
+        if !self.self_window {
+            return Ok(message);
+        }
+
+        Ok(message.kitty_window_id(own_window_id("set-window-title: self_window")?))
+    }
+}
+
+/// The id of the kitty window this process runs in, from `KITTY_WINDOW_ID`.
+/// `what` names the option needing it in the error.
+fn own_window_id(what: &str) -> Result<String, CommandError> {
+    std::env::var("KITTY_WINDOW_ID").map_err(|_| {
+        CommandError::ValidationError(format!(
+            "{} requires KITTY_WINDOW_ID; not running inside kitty",
+            what
+        ))
+    })
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Read the PNG at `path` and base64 encode it for the `data` field of
+/// `set-window-logo` and `set-background-image`. kitty only accepts PNG,
+/// so anything else fails with [`io::ErrorKind::InvalidData`]. kitty
+/// decodes each streamed chunk on its own, which works because the 4 KiB
+/// chunk size is a multiple of four base64 characters.
+pub(crate) fn read_png_data<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let bytes = std::fs::read(path)?;
+    if !bytes.starts_with(PNG_SIGNATURE) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "image is not a PNG file",
+        ));
+    }
+    Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
 pub struct SetWindowLogoCommand {
     match_spec: Option<String>,
     data: Option<String>,
@@ -801,8 +1345,8 @@ impl SetWindowLogoCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -811,6 +1355,19 @@ impl SetWindowLogoCommand {
         self
     }
 
+    /// Use the PNG at `path` as the logo. Images over the 4 KiB chunk
+    /// size are streamed by `Kitty::execute_all`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new().data(read_png_data(path)?))
+    }
+
+    /// Remove the logo instead of setting one, by sending kitty's `-`
+    /// data. kitty has no command to read the current logo back, so
+    /// toggling is up to the caller.
+    pub fn clear(self) -> Self {
+        self.data("-")
+    }
+
     pub fn position(mut self, value: impl Into<String>) -> Self {
         self.position = Some(value.into());
         self
@@ -863,6 +1420,7 @@ pub struct GetTextCommand {
     wrap_markers: bool,
     clear_selection: bool,
     self_window: bool,
+    self_capture: bool,
 }
 
 impl GetTextCommand {
@@ -875,11 +1433,24 @@ impl GetTextCommand {
             wrap_markers: false,
             clear_selection: false,
             self_window: false,
+            self_capture: false,
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    /// Capture the window this process runs in rather than the active
+    /// window, which is what kitty picks when there is no match.
+    ///
+    /// Sets `self` and tags the message with `KITTY_WINDOW_ID` so kitty
+    /// knows which window that is. `build` fails if the variable is not
+    /// set, i.e. when not running inside kitty.
+    pub fn self_capture(mut self) -> Self {
+        self.self_window = true;
+        self.self_capture = true;
+        self
+    }
+
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -944,9 +1515,69 @@ impl GetTextCommand {
             payload.insert("self".to_string(), serde_json::Value::Bool(true));
         }
 
-        Ok(CommandBuilder::new("get-text")
+        let message = CommandBuilder::new("get-text")
             .payload(serde_json::Value::Object(payload))
-            .build())
+            .build();
+
+        if !self.self_capture {
+            return Ok(message);
+        }
+
+        Ok(message.kitty_window_id(own_window_id("get-text: self_capture")?))
+    }
+
+    /// The captured text of a single window, ANSI escapes included when
+    /// requested with [`ansi`](Self::ansi).
+    ///
+    /// kitty answers with one string for the window it picked, returned as
+    /// is: kitty never encodes it a second time. Text keyed by
+    /// window id, as some broad matches produce, is rejected with a pointer
+    /// to [`parse_response_by_window`](Self::parse_response_by_window)
+    /// rather than silently dropping all but one window.
+    pub fn parse_response(response: &KittyResponse) -> Result<String, ProtocolError> {
+        match &response.data {
+            Some(Value::String(text)) => Ok(text.clone()),
+            Some(Value::Object(windows)) => Err(ProtocolError::PayloadValidationError(format!(
+                "get-text returned text for {} windows; use parse_response_by_window",
+                windows.len()
+            ))),
+            _ => Err(ProtocolError::MissingField("data".to_string())),
+        }
+    }
+
+    /// The captured text of each window, for responses keyed by window id
+    /// (`{"<id>": "<text>", ...}`).
+    pub fn parse_response_by_window(
+        response: &KittyResponse,
+    ) -> Result<BTreeMap<u64, String>, ProtocolError> {
+        let windows = match &response.data {
+            Some(Value::Object(windows)) => windows,
+            Some(Value::String(_)) => {
+                return Err(ProtocolError::PayloadValidationError(
+                    "get-text returned text for a single window; use parse_response".to_string(),
+                ));
+            }
+            _ => return Err(ProtocolError::MissingField("data".to_string())),
+        };
+
+        windows
+            .iter()
+            .map(|(id, text)| {
+                let id = id.parse().map_err(|_| {
+                    ProtocolError::PayloadValidationError(format!(
+                        "get-text: '{}' is not a window id",
+                        id
+                    ))
+                })?;
+                let text = text.as_str().ok_or_else(|| {
+                    ProtocolError::PayloadValidationError(format!(
+                        "get-text: text for window {} is not a string",
+                        id
+                    ))
+                })?;
+                Ok((id, text.to_string()))
+            })
+            .collect()
     }
 }
 
@@ -963,8 +1594,8 @@ impl ScrollWindowCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -998,8 +1629,8 @@ impl CreateMarkerCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -1050,8 +1681,8 @@ impl RemoveMarkerCommand {
         }
     }
 
-    pub fn match_spec(mut self, spec: impl Into<String>) -> Self {
-        self.match_spec = Some(spec.into());
+    pub fn match_spec(mut self, spec: impl Into<MatchSpec>) -> Self {
+        self.match_spec = Some(spec.into().into());
         self
     }
 
@@ -1116,6 +1747,53 @@ mod tests {
         assert_eq!(msg.cmd, "send-text");
     }
 
+    #[test]
+    fn test_send_text_from_reader() {
+        let msg = SendTextCommand::from_reader("echo hi\n".as_bytes())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(msg.payload.unwrap()["data"], "text:echo hi\n");
+
+        let invalid: &[u8] = &[0xff, 0xfe];
+        assert_eq!(
+            SendTextCommand::from_reader(invalid).err().unwrap().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_send_text_from_file_chunks() {
+        let path = std::env::temp_dir().join(format!("kitty-rc-send-text-{}", std::process::id()));
+        let text = "x\u{e9}".repeat(3000);
+        std::fs::write(&path, &text).unwrap();
+        let chunks = SendTextCommand::from_file(&path)
+            .unwrap()
+            .match_spec("id:3")
+            .all(true)
+            .bracketed_paste("enable")
+            .build_all()
+            .unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        // 9000 bytes of text: three complete send-text commands.
+        assert_eq!(chunks.len(), 3);
+        let mut joined = String::new();
+        for chunk in &chunks {
+            assert_eq!(chunk.cmd, "send-text");
+            assert!(chunk.stream_id.is_none());
+            let payload = chunk.payload.as_ref().unwrap();
+            assert_eq!(payload["match"], "id:3");
+            assert_eq!(payload["all"], true);
+            assert_eq!(payload["bracketed_paste"], "enable");
+            let data = payload["data"].as_str().unwrap();
+            joined.push_str(data.strip_prefix("text:").unwrap());
+        }
+        assert_eq!(joined, text);
+
+        assert!(SendTextCommand::from_file(std::env::temp_dir().join("kitty-rc-missing")).is_err());
+    }
+
     #[test]
     fn test_send_text_empty() {
         let cmd = SendTextCommand::new("").build();
@@ -1139,6 +1817,76 @@ mod tests {
         assert_eq!(msg.cmd, "send-text");
     }
 
+    #[test]
+    fn test_send_text_broadcast_except_active() {
+        let msg = SendTextCommand::new("text:hi")
+            .broadcast_except_active()
+            .build()
+            .unwrap();
+        let payload = msg.payload.unwrap();
+        assert_eq!(payload["all"], true);
+        assert_eq!(payload["exclude_active"], true);
+    }
+
+    #[test]
+    fn test_send_text_exclude_active_alone_rejected() {
+        let cmd = SendTextCommand::new("hi").exclude_active(true).build();
+        assert!(matches!(cmd, Err(CommandError::ValidationError(_))));
+
+        let cmd = SendTextCommand::new("hi")
+            .match_tab("title:logs")
+            .exclude_active(true)
+            .build();
+        assert!(cmd.is_ok());
+    }
+
+    #[test]
+    fn test_send_text_only_at_prompt() {
+        let msg = SendTextCommand::new("ls\n")
+            .match_spec("id:1")
+            .only_at_prompt(true)
+            .build()
+            .unwrap();
+        assert!(msg.only_at_prompt);
+        assert!(
+            serde_json::to_value(&msg)
+                .unwrap()
+                .get("only_at_prompt")
+                .is_none()
+        );
+
+        assert!(matches!(
+            SendTextCommand::new("ls\n")
+                .all(true)
+                .only_at_prompt(true)
+                .build(),
+            Err(CommandError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_send_text_line_ending() {
+        let data = |ending| {
+            let msg = SendTextCommand::new("text:ls\r\npwd\n")
+                .line_ending(ending)
+                .build()
+                .unwrap();
+            msg.payload.unwrap()["data"].as_str().unwrap().to_string()
+        };
+
+        assert_eq!(data(LineEnding::Keep), "text:ls\r\npwd\n");
+        assert_eq!(data(LineEnding::Lf), "text:ls\npwd\n");
+        assert_eq!(data(LineEnding::Cr), "text:ls\rpwd\r");
+        assert_eq!(data(LineEnding::CrLf), "text:ls\r\npwd\r\n");
+    }
+
+    #[test]
+    fn test_line_ending_apply() {
+        assert_eq!(LineEnding::Lf.apply("a\rb\r\nc"), "a\nb\nc");
+        assert_eq!(LineEnding::CrLf.apply("a\nb"), "a\r\nb");
+        assert_eq!(LineEnding::default(), LineEnding::Keep);
+    }
+
     #[test]
     fn test_send_key_basic() {
         let cmd = SendKeyCommand::new("ctrl+c").build();
@@ -1170,6 +1918,54 @@ mod tests {
         assert_eq!(msg.cmd, "send-key");
     }
 
+    #[test]
+    fn test_send_text_and_key_self_window() {
+        let payload = SendTextCommand::new("text:hi")
+            .self_window(true)
+            .build()
+            .unwrap()
+            .payload
+            .unwrap();
+        assert_eq!(payload["self"], true);
+        assert!(
+            SendTextCommand::new("text:hi")
+                .build()
+                .unwrap()
+                .payload
+                .unwrap()
+                .get("self")
+                .is_none()
+        );
+        assert!(
+            SendTextCommand::new("text:hi")
+                .self_window(true)
+                .only_at_prompt(true)
+                .build()
+                .is_err()
+        );
+
+        let payload = SendKeyCommand::new("ctrl+c")
+            .self_window(true)
+            .exclude_active(true)
+            .build()
+            .unwrap()
+            .payload
+            .unwrap();
+        assert_eq!(payload["self"], true);
+        assert_eq!(payload["exclude_active"], true);
+    }
+
+    #[test]
+    fn test_send_key_broadcast_except_active() {
+        let msg = SendKeyCommand::new("ctrl+c")
+            .broadcast_except_active()
+            .build()
+            .unwrap();
+        let payload = msg.payload.unwrap();
+        assert_eq!(payload["all"], true);
+        assert_eq!(payload["exclude_active"], true);
+    }
+
     #[test]
     fn test_close_window_basic() {
         let cmd = CloseWindowCommand::new().build();
@@ -1210,6 +2006,18 @@ mod tests {
         assert_eq!(msg.cmd, "resize-window");
     }
 
+    #[test]
+    fn test_send_key_exclude_active_alone_rejected() {
+        let cmd = SendKeyCommand::new("ctrl+c").exclude_active(true).build();
+        assert!(matches!(cmd, Err(CommandError::ValidationError(_))));
+
+        let cmd = SendKeyCommand::new("ctrl+c")
+            .match_spec("state:needs_attention")
+            .exclude_active(true)
+            .build();
+        assert!(cmd.is_ok());
+    }
+
     #[test]
     fn test_focus_window_basic() {
         let cmd = FocusWindowCommand::new().build();
@@ -1271,6 +2079,19 @@ mod tests {
         assert_eq!(msg.cmd, "new-window");
     }
 
+    #[test]
+    fn test_new_window_parse_response() {
+        let ok: KittyResponse = serde_json::from_str(r#"{"ok":true,"data":"12"}"#).unwrap();
+        assert_eq!(NewWindowCommand::parse_response(&ok).unwrap(), WindowId(12));
+
+        let failed: KittyResponse =
+            serde_json::from_str(r#"{"ok":false,"error":"no such tab"}"#).unwrap();
+        assert!(matches!(
+            NewWindowCommand::parse_response(&failed),
+            Err(CommandError::KittyError(cmd, _)) if cmd == "new-window"
+        ));
+    }
+
     #[test]
     fn test_detach_window_basic() {
         let cmd = DetachWindowCommand::new().build();
@@ -1323,6 +2144,78 @@ mod tests {
         assert_eq!(msg.cmd, "set-window-title");
     }
 
+    #[test]
+    fn test_set_os_window_title_basic() {
+        let msg = SetOsWindowTitleCommand::new("Work").build().unwrap();
+        assert_eq!(msg.cmd, "set-window-title");
+        let payload = msg.payload.unwrap();
+        assert_eq!(payload["title"], "Work");
+        assert_eq!(payload["match"], "state:focused");
+        assert_eq!(payload["temporary"], true);
+        assert!(msg.kitty_window_id.is_none());
+    }
+
+    #[test]
+    fn test_set_os_window_title_with_options() {
+        let msg = SetOsWindowTitleCommand::new("Work")
+            .match_spec("id:3")
+            .build()
+            .unwrap();
+        assert_eq!(msg.payload.unwrap()["match"], "id:3");
+    }
+
+    #[test]
+    fn test_set_os_window_title_empty() {
+        let cmd = SetOsWindowTitleCommand::new("").build();
+        assert!(matches!(cmd, Err(CommandError::MissingParameter(_, _))));
+    }
+
+    #[test]
+    fn test_parse_os_instance_fields() {
+        let instances = parse_response_data(&serde_json::json!([
+            {
+                "id": 1,
+                "is_focused": true,
+                "is_active": true,
+                "platform_window_id": 8388621,
+                "wm_class": "kitty",
+                "wm_name": "kitty",
+                "tabs": []
+            },
+            {"id": 2}
+        ]))
+        .unwrap();
+
+        let focused = &instances[0];
+        assert_eq!(focused.id, Some(OsWindowId(1)));
+        assert_eq!(focused.is_focused, Some(true));
+        assert_eq!(focused.platform_window_id, Some(8388621));
+        assert_eq!(focused.wm_class.as_deref(), Some("kitty"));
+        assert_eq!(focused.wm_name.as_deref(), Some("kitty"));
+
+        let bare = &instances[1];
+        assert_eq!(bare.id, Some(OsWindowId(2)));
+        assert_eq!(bare.is_focused, None);
+        assert_eq!(bare.platform_window_id, None);
+        assert_eq!(bare.wm_class, None);
+        assert!(bare.tabs.is_empty());
+    }
+
+    #[test]
+    fn test_os_instance_title() {
+        let json_data = serde_json::json!([{
+            "tabs": [
+                {"is_active": false, "title": "other", "windows": [{"id": 1, "title": "a", "is_active": true}]},
+                {"is_active": true, "title": "tab", "windows": [
+                    {"id": 2, "title": "b", "is_active": false},
+                    {"id": 3, "title": "c", "is_active": true}
+                ]}
+            ]
+        }]);
+        let instances = parse_response_data(&json_data).unwrap();
+        assert_eq!(instances[0].title(), Some("c"));
+    }
+
     #[test]
     fn test_set_window_logo_basic() {
         let cmd = SetWindowLogoCommand::new().build();
@@ -1331,6 +2224,59 @@ mod tests {
         assert_eq!(msg.cmd, "set-window-logo");
     }
 
+    #[test]
+    fn test_set_window_logo_clear() {
+        let msg = SetWindowLogoCommand::new()
+            .data("base64data")
+            .clear()
+            .match_spec("id:1")
+            .build()
+            .unwrap();
+        let payload = msg.payload.unwrap();
+        assert_eq!(payload["data"], "-");
+        assert_eq!(payload["match"], "id:1");
+    }
+
+    #[test]
+    fn test_set_window_logo_from_file() {
+        let dir = std::env::temp_dir();
+        let png = dir.join(format!("kitty-rc-logo-{}.png", std::process::id()));
+        let mut bytes = PNG_SIGNATURE.to_vec();
+        bytes.extend(std::iter::repeat_n(7u8, 6000));
+        std::fs::write(&png, &bytes).unwrap();
+        let msg = SetWindowLogoCommand::from_file(&png)
+            .unwrap()
+            .match_spec("id:3")
+            .position("top-left")
+            .build()
+            .unwrap();
+        let _ = std::fs::remove_file(&png);
+
+        let data = msg.payload.as_ref().unwrap()["data"].as_str().unwrap();
+        assert_eq!(
+            base64::engine::general_purpose::STANDARD
+                .decode(data)
+                .unwrap(),
+            bytes
+        );
+        assert!(msg.needs_streaming());
+
+        // Streamed chunks still target the matched window.
+        let chunks = msg.into_chunks();
+        assert_eq!(chunks.len(), 3);
+        for chunk in &chunks {
+            let payload = chunk.payload.as_ref().unwrap();
+            assert_eq!(payload["match"], "id:3");
+            assert_eq!(payload["position"], "top-left");
+        }
+
+        let text = dir.join(format!("kitty-rc-logo-{}.txt", std::process::id()));
+        std::fs::write(&text, "not an image").unwrap();
+        let err = SetWindowLogoCommand::from_file(&text).err().unwrap();
+        let _ = std::fs::remove_file(&text);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
     #[test]
     fn test_set_window_logo_with_options() {
         let cmd = SetWindowLogoCommand::new()
@@ -1353,6 +2299,34 @@ mod tests {
         assert_eq!(msg.cmd, "get-text");
     }
 
+    #[test]
+    fn test_own_window_targeting() {
+        // SAFETY: no other test reads or writes KITTY_WINDOW_ID; the
+        // commands needing it are all checked here.
+        unsafe { std::env::set_var("KITTY_WINDOW_ID", "42") };
+        let msg = GetTextCommand::new().self_capture().build().unwrap();
+        assert_eq!(msg.kitty_window_id.as_deref(), Some("42"));
+        assert_eq!(msg.payload.unwrap()["self"], true);
+
+        let msg = SetOsWindowTitleCommand::new("Work")
+            .self_window(true)
+            .build()
+            .unwrap();
+        assert_eq!(msg.kitty_window_id.as_deref(), Some("42"));
+        assert!(msg.payload.unwrap().get("match").is_none());
+
+        unsafe { std::env::remove_var("KITTY_WINDOW_ID") };
+        let cmd = GetTextCommand::new().self_capture().build();
+        assert!(matches!(cmd, Err(CommandError::ValidationError(_))));
+        let cmd = SetOsWindowTitleCommand::new("Work")
+            .self_window(true)
+            .build();
+        assert!(matches!(cmd, Err(CommandError::ValidationError(_))));
+
+        let msg = GetTextCommand::new().build().unwrap();
+        assert!(msg.kitty_window_id.is_none());
+    }
+
     #[test]
     fn test_get_text_with_options() {
         let cmd = GetTextCommand::new()
@@ -1369,6 +2343,78 @@ mod tests {
         assert_eq!(msg.cmd, "get-text");
     }
 
+    #[test]
+    fn test_get_text_parse_response() {
+        let response = |data: Value| KittyResponse {
+            ok: true,
+            data: Some(data),
+            error: None,
+        };
+
+        let single = response(Value::String("$ ls\n".to_string()));
+        assert_eq!(GetTextCommand::parse_response(&single).unwrap(), "$ ls\n");
+        assert!(matches!(
+            GetTextCommand::parse_response_by_window(&single),
+            Err(ProtocolError::PayloadValidationError(_))
+        ));
+
+        let keyed = response(serde_json::json!({"3": "one", "12": "two"}));
+        let by_window = GetTextCommand::parse_response_by_window(&keyed).unwrap();
+        assert_eq!(
+            by_window.into_iter().collect::<Vec<_>>(),
+            [(3, "one".to_string()), (12, "two".to_string())]
+        );
+        match GetTextCommand::parse_response(&keyed) {
+            Err(ProtocolError::PayloadValidationError(msg)) => {
+                assert!(msg.contains("2 windows"));
+                assert!(msg.contains("parse_response_by_window"));
+            }
+            other => panic!("expected PayloadValidationError, got {:?}", other),
+        }
+
+        // A screen showing a JSON string literal is still plain text.
+        let literal = response(Value::String("\"foo\"\n".to_string()));
+        assert_eq!(
+            GetTextCommand::parse_response(&literal).unwrap(),
+            "\"foo\"\n"
+        );
+
+        let quoted = response(Value::String(r#""not json"#.to_string()));
+        assert_eq!(
+            GetTextCommand::parse_response(&quoted).unwrap(),
+            r#""not json"#
+        );
+
+        let bad_key = response(serde_json::json!({"main": "text"}));
+        assert!(GetTextCommand::parse_response_by_window(&bad_key).is_err());
+
+        let empty = KittyResponse {
+            ok: true,
+            data: None,
+            error: None,
+        };
+        assert!(matches!(
+            GetTextCommand::parse_response(&empty),
+            Err(ProtocolError::MissingField(_))
+        ));
+    }
+
+    #[test]
+    fn test_get_text_parse_response_ansi() {
+        let text = "\x1b[32m$\x1b[m ls\r\n\x1b[1;34msrc\x1b[m\r\n";
+        let response = KittyResponse {
+            ok: true,
+            data: Some(Value::String(text.to_string())),
+            error: None,
+        };
+        assert_eq!(GetTextCommand::parse_response(&response).unwrap(), text);
+
+        // the same text as it arrives on the wire, escapes and all
+        let body = serde_json::json!({"ok": true, "data": text}).to_string();
+        let response: KittyResponse = serde_json::from_str(&body).unwrap();
+        assert_eq!(GetTextCommand::parse_response(&response).unwrap(), text);
+    }
+
     #[test]
     fn test_scroll_window_basic() {
         let cmd = ScrollWindowCommand::new(5).build();
@@ -1455,13 +2501,300 @@ mod tests {
         assert_eq!(instances.len(), 1);
         assert_eq!(instances[0].tabs.len(), 1);
         assert_eq!(instances[0].tabs[0].windows.len(), 1);
-        assert_eq!(instances[0].tabs[0].windows[0].id, Some(1));
+        assert_eq!(instances[0].tabs[0].windows[0].id, Some(WindowId(1)));
         assert_eq!(
             instances[0].tabs[0].windows[0].title,
             Some("Test Window".to_string())
         );
     }
 
+    #[cfg(feature = "raw-value")]
+    #[test]
+    fn test_parse_response_raw_matches_value_path() {
+        let tree = serde_json::json!([{
+            "id": 1,
+            "is_focused": true,
+            "tabs": [{
+                "id": 2,
+                "title": "t\"1",
+                "windows": [{"id": 3, "title": "vim", "cmdline": ["vim"], "at_prompt": false}]
+            }]
+        }]);
+
+        for data in [tree.clone(), serde_json::Value::String(tree.to_string())] {
+            let response = KittyResponse {
+                ok: true,
+                data: Some(data),
+                error: None,
+            };
+            let body = serde_json::to_string(&response).unwrap();
+
+            assert_eq!(
+                format!("{:?}", LsCommand::parse_response_raw(&body).unwrap()),
+                format!("{:?}", LsCommand::parse_response(&response).unwrap())
+            );
+        }
+
+        assert!(
+            LsCommand::parse_response_raw(r#"{"ok": true}"#)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    fn snapshot(windows: serde_json::Value) -> Vec<OsInstance> {
+        parse_response_data(&serde_json::json!([{"tabs": [{"windows": windows}]}])).unwrap()
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let instances = snapshot(serde_json::json!([{
+            "id": 7,
+            "title": "shell",
+            "is_focused": true,
+            "env": {"TERM": "xterm-kitty"},
+            "foreground_processes": [{"pid": 42, "cmdline": ["vim"]}]
+        }]));
+
+        let json = serde_json::to_value(&instances).unwrap();
+        let window = &json[0]["tabs"][0]["windows"][0];
+        assert_eq!(window["id"], 7);
+        assert_eq!(window["env"]["TERM"], "xterm-kitty");
+        assert_eq!(window["foreground_processes"][0]["pid"], 42);
+
+        let reparsed = parse_response_data(&json).unwrap();
+        let window = &reparsed[0].tabs[0].windows[0];
+        assert_eq!(window.title.as_deref(), Some("shell"));
+        assert_eq!(window.is_focused, Some(true));
+    }
+
+    #[test]
+    fn test_diff_windows_opened_and_closed() {
+        let before = snapshot(serde_json::json!([{"id": 1}, {"id": 2}]));
+        let after = snapshot(serde_json::json!([{"id": 2}, {"id": 3}]));
+
+        let diff = diff_windows(&before, &after);
+        assert_eq!(diff.opened, vec![WindowId(3)]);
+        assert_eq!(diff.closed, vec![WindowId(1)]);
+        assert_eq!(diff.focus_changed, None);
+    }
+
+    #[test]
+    fn test_diff_windows_focus_changed() {
+        let before = snapshot(serde_json::json!([
+            {"id": 1, "is_focused": true},
+            {"id": 2, "is_focused": false}
+        ]));
+        let after = snapshot(serde_json::json!([
+            {"id": 1, "is_focused": false},
+            {"id": 2, "is_focused": true}
+        ]));
+
+        let diff = diff_windows(&before, &after);
+        assert!(diff.opened.is_empty() && diff.closed.is_empty());
+        assert_eq!(
+            diff.focus_changed,
+            Some((Some(WindowId(1)), Some(WindowId(2))))
+        );
+    }
+
+    #[test]
+    fn test_diff_windows_focused_window_closed() {
+        let before = snapshot(serde_json::json!([{"id": 1, "is_focused": true}]));
+        let after = snapshot(serde_json::json!([]));
+
+        let diff = diff_windows(&before, &after);
+        assert_eq!(diff.closed, vec![WindowId(1)]);
+        assert_eq!(diff.focus_changed, Some((Some(WindowId(1)), None)));
+    }
+
+    #[test]
+    fn test_diff_windows_unchanged() {
+        let before = snapshot(serde_json::json!([{"id": 1, "is_focused": true}]));
+        let after = snapshot(serde_json::json!([{"id": 1, "is_focused": true}]));
+        assert!(diff_windows(&before, &after).is_empty());
+    }
+
+    #[test]
+    fn test_launch_cmdline() {
+        let instances = snapshot(serde_json::json!([
+            {
+                "id": 1,
+                "pid": 100,
+                "cmdline": ["nvim", "notes.md"],
+                "foreground_processes": [{"pid": 100, "cmdline": ["nvim", "notes.md"]}],
+                "last_reported_cmdline": "git status"
+            },
+            {
+                "id": 2,
+                "pid": 200,
+                "foreground_processes": [
+                    {"pid": 201, "cmdline": ["htop"]},
+                    {"pid": 200, "cmdline": ["/bin/zsh", "-l"]}
+                ]
+            },
+            {"id": 3, "pid": 300}
+        ]));
+        let windows = &instances[0].tabs[0].windows;
+
+        assert_eq!(
+            windows[0].launch_cmdline(),
+            Some(&["nvim".to_string(), "notes.md".to_string()][..])
+        );
+        assert_eq!(
+            windows[1].launch_cmdline(),
+            Some(&["/bin/zsh".to_string(), "-l".to_string()][..])
+        );
+        assert_eq!(windows[2].launch_cmdline(), None);
+    }
+
+    #[test]
+    fn test_parse_window_state_fields() {
+        let instances = snapshot(serde_json::json!([
+            {
+                "id": 1,
+                "is_focused": true,
+                "is_active": true,
+                "is_self": false,
+                "lines": 40,
+                "columns": 120,
+                "env": {"TERM": "xterm-kitty"},
+                "at_prompt": true,
+                "user_vars": {"project": "kitty-rc"},
+                "last_reported_cmdline": "cargo test"
+            },
+            {"id": 2}
+        ]));
+        let windows = &instances[0].tabs[0].windows;
+
+        let full = &windows[0];
+        assert_eq!(full.is_focused, Some(true));
+        assert_eq!(full.is_active, Some(true));
+        assert_eq!(full.is_self, Some(false));
+        assert_eq!((full.lines, full.columns), (Some(40), Some(120)));
+        assert_eq!(full.env["TERM"], "xterm-kitty");
+        assert_eq!(full.at_prompt, Some(true));
+        assert_eq!(full.user_vars["project"], "kitty-rc");
+        assert_eq!(full.last_reported_cmdline.as_deref(), Some("cargo test"));
+
+        // older kitty versions omit these, which must not fail the parse
+        let bare = &windows[1];
+        assert_eq!(bare.is_focused, None);
+        assert_eq!(bare.lines, None);
+        assert!(bare.env.is_empty());
+        assert!(bare.user_vars.is_empty());
+        assert_eq!(bare.last_reported_cmdline, None);
+    }
+
+    #[test]
+    fn test_parse_at_prompt() {
+        let instances = snapshot(serde_json::json!([
+            {"id": 1, "at_prompt": true},
+            {"id": 2, "at_prompt": false},
+            {"id": 3}
+        ]));
+        let windows = &instances[0].tabs[0].windows;
+
+        assert_eq!(windows[0].at_prompt, Some(true));
+        assert_eq!(windows[1].at_prompt, Some(false));
+        assert_eq!(windows[2].at_prompt, None);
+    }
+
+    #[test]
+    fn test_parse_active_window_history() {
+        let instances = parse_response_data(&serde_json::json!([{"tabs": [{
+            "id": 1,
+            "active_window_history": [3, 1, 2],
+            "windows": [{"id": 1}, {"id": 2}, {"id": 3}]
+        }, {
+            "id": 2
+        }]}]))
+        .unwrap();
+
+        assert_eq!(instances[0].tabs[0].active_window_history, vec![3, 1, 2]);
+        assert!(instances[0].tabs[1].active_window_history.is_empty());
+    }
+
+    #[test]
+    fn test_parse_tab_fields() {
+        let instances = parse_response_data(&serde_json::json!([{"tabs": [{
+            "id": 4,
+            "title": "editor",
+            "layout": "tall",
+            "is_focused": true,
+            "is_active": true,
+            "enabled_layouts": ["tall", "stack"],
+            "windows": [{"id": 1}]
+        }, {}]}]))
+        .unwrap();
+        let tabs = &instances[0].tabs;
+
+        assert_eq!(tabs[0].id, Some(TabId(4)));
+        assert_eq!(tabs[0].title.as_deref(), Some("editor"));
+        assert_eq!(tabs[0].layout.as_deref(), Some("tall"));
+        assert_eq!(tabs[0].is_focused, Some(true));
+        assert_eq!(tabs[0].enabled_layouts, ["tall", "stack"]);
+
+        assert_eq!(tabs[1].id, None);
+        assert_eq!(tabs[1].title, None);
+        assert_eq!(tabs[1].layout, None);
+        assert!(tabs[1].enabled_layouts.is_empty());
+        assert!(tabs[1].windows.is_empty());
+    }
+
+    #[test]
+    fn test_split_tree() {
+        let instances = parse_response_data(&serde_json::json!([{"tabs": [{
+            "layout": "splits",
+            "windows": [{"id": 10}, {"id": 11}, {"id": 12}, {"id": 13}],
+            "layout_state": {
+                "all_windows": {
+                    "window_groups": [
+                        {"id": 1, "window_ids": [10]},
+                        {"id": 2, "window_ids": [11, 13]},
+                        {"id": 3, "window_ids": [12]}
+                    ]
+                },
+                "pairs": {
+                    "horizontal": true,
+                    "bias": 0.6,
+                    "one": 1,
+                    "two": {"horizontal": false, "one": 2, "two": 3}
+                }
+            }
+        }]}]))
+        .unwrap();
+
+        let leaf = |group, windows: &[u64]| SplitTree::Leaf {
+            group,
+            windows: windows.to_vec(),
+        };
+        assert_eq!(
+            instances[0].tabs[0].split_tree(),
+            Some(SplitTree::Split {
+                horizontal: true,
+                bias: 0.6,
+                first: Box::new(leaf(1, &[10])),
+                second: Box::new(SplitTree::Split {
+                    horizontal: false,
+                    bias: 0.5,
+                    first: Box::new(leaf(2, &[11, 13])),
+                    second: Box::new(leaf(3, &[12])),
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_split_tree_other_layout() {
+        let instances = parse_response_data(&serde_json::json!([{"tabs": [{
+            "layout": "tall",
+            "layout_state": {"main_bias": [0.5]}
+        }]}]))
+        .unwrap();
+        assert_eq!(instances[0].tabs[0].split_tree(), None);
+    }
+
     #[test]
     fn test_parse_ls_response_empty() {
         let response = KittyResponse {
@@ -1473,4 +2806,122 @@ mod tests {
         let instances = LsCommand::parse_response(&response).unwrap();
         assert!(instances.is_empty());
     }
+
+    #[test]
+    fn test_parse_focused_id() {
+        let tree = serde_json::json!([
+            {"id": 1, "tabs": [{"id": 2, "windows": [
+                {"id": 3, "is_focused": false, "cmdline": ["zsh"]},
+                {"id": 4, "is_focused": true, "foreground_processes": [{"pid": 9}]}
+            ]}]}
+        ]);
+        let response = |data: Option<Value>| KittyResponse {
+            ok: true,
+            data,
+            error: None,
+        };
+
+        let encoded = response(Some(Value::String(tree.to_string())));
+        assert_eq!(
+            LsCommand::parse_focused_id(&encoded).unwrap(),
+            Some(WindowId(4))
+        );
+        assert_eq!(
+            LsCommand::parse_focused_id(&response(Some(tree))).unwrap(),
+            Some(WindowId(4))
+        );
+        assert_eq!(LsCommand::parse_focused_id(&response(None)).unwrap(), None);
+        assert!(LsCommand::parse_focused_id(&response(Some(serde_json::json!({})))).is_err());
+    }
+
+    #[test]
+    fn test_self_window() {
+        let instances = snapshot(serde_json::json!([
+            {"id": 1, "is_focused": true, "is_self": false},
+            {"id": 2, "is_focused": false, "is_self": true},
+            {"id": 3}
+        ]));
+
+        assert_eq!(
+            self_window(&instances).and_then(|w| w.id),
+            Some(WindowId(2))
+        );
+        assert_eq!(
+            active_window(&instances).and_then(|w| w.id),
+            Some(WindowId(1))
+        );
+        assert_eq!(instances[0].tabs[0].windows[2].is_self, None);
+    }
+
+    #[test]
+    fn test_active_in_tab_vs_focused() {
+        let instances = parse_response_data(&serde_json::json!([
+            {"id": 1, "tabs": [{"windows": [
+                {"id": 10, "is_active": true, "is_focused": false},
+                {"id": 11, "is_active": false, "is_focused": false}
+            ]}]},
+            {"id": 2, "tabs": [{"windows": [
+                {"id": 20, "is_active": false, "is_focused": false},
+                {"id": 21, "is_active": true, "is_focused": true}
+            ]}]}
+        ]))
+        .unwrap();
+
+        let background = &instances[0].tabs[0].windows[0];
+        assert_eq!(background.is_active, Some(true));
+        assert_eq!(background.is_focused, Some(false));
+
+        assert_eq!(
+            active_window(&instances).and_then(|w| w.id),
+            Some(WindowId(21))
+        );
+    }
+
+    #[test]
+    fn test_self_window_absent() {
+        let instances = snapshot(serde_json::json!([{"id": 1}, {"id": 2}]));
+
+        assert!(self_window(&instances).is_none());
+        assert!(active_window(&instances).is_none());
+    }
+
+    #[test]
+    fn test_all_windows() {
+        let instances = parse_response_data(&serde_json::json!([
+            {"id": 1, "tabs": [
+                {"id": 5, "windows": [{"id": 10, "pid": 100}, {"id": 11, "pid": 101}]},
+                {"id": 6, "windows": []}
+            ]},
+            {"id": 2, "tabs": [{"id": 7, "windows": [{"id": 20, "pid": 200}]}]}
+        ]))
+        .unwrap();
+
+        let ids: Vec<_> = all_windows(&instances).filter_map(|w| w.id).collect();
+        assert_eq!(ids, vec![WindowId(10), WindowId(11), WindowId(20)]);
+        assert_eq!(
+            all_windows(&instances)
+                .find(|w| w.pid == Some(200))
+                .and_then(|w| w.id),
+            Some(WindowId(20))
+        );
+
+        let located: Vec<_> = all_windows_with_ids(&instances)
+            .map(|(os_id, tab_id, w)| {
+                (
+                    os_id.map(u64::from),
+                    tab_id.map(u64::from),
+                    w.id.map(u64::from),
+                )
+            })
+            .collect();
+        assert_eq!(
+            located,
+            vec![
+                (Some(1), Some(5), Some(10)),
+                (Some(1), Some(5), Some(11)),
+                (Some(2), Some(7), Some(20)),
+            ]
+        );
+        assert_eq!(all_windows(&[]).count(), 0);
+    }
 }
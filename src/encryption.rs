@@ -59,7 +59,19 @@ impl Encryptor {
 
         let mut key_array = [0u8; 32];
         key_array.copy_from_slice(&key_bytes[..32]);
-        Ok(PublicKey::from(key_array))
+        let public_key = PublicKey::from(key_array);
+
+        // A small-order point (the all-zero key among them) yields the same
+        // all-zero shared secret for every secret key. Clamped scalars are
+        // multiples of the cofactor, so a fixed one is enough to detect it.
+        if !StaticSecret::from([1u8; 32])
+            .diffie_hellman(&public_key)
+            .was_contributory()
+        {
+            return Err(EncryptionError::InsecurePublicKey);
+        }
+
+        Ok(public_key)
     }
 
     /// Read kitty's public key from environment.
@@ -192,6 +204,23 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_new_with_public_key_small_order() {
+        let zero_key = format!("1:{}", base85::encode(&[0u8; 32]));
+        assert!(matches!(
+            Encryptor::new_with_public_key(Some(&zero_key)),
+            Err(EncryptionError::InsecurePublicKey)
+        ));
+
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        let one_key = format!("1:{}", base85::encode(&one));
+        assert!(matches!(
+            Encryptor::new_with_public_key(Some(&one_key)),
+            Err(EncryptionError::InsecurePublicKey)
+        ));
+    }
+
     #[test]
     fn test_new_with_public_key_none() {
         let secret = StaticSecret::random_from_rng(&mut OsRng);
@@ -85,6 +85,12 @@ pub enum EncryptionError {
 
     #[error("Public key database query failed: {0}")]
     PublicKeyDatabaseError(String),
+
+    #[error("Password unavailable: {0}")]
+    PasswordUnavailable(String),
+
+    #[error("Insecure public key: small-order X25519 point")]
+    InsecurePublicKey,
 }
 
 /// Errors related to connection, transport, and I/O
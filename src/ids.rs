@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+macro_rules! define_id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(
+            Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+        )]
+        #[serde(transparent)]
+        pub struct $name(pub u64);
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl From<u64> for $name {
+            fn from(id: u64) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<$name> for u64 {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+    };
+}
+
+define_id!(
+    /// The id kitty gives a window, as in `ls` output and `id:` matches.
+    WindowId
+);
+define_id!(
+    /// The id kitty gives a tab. Tab ids and window ids are separate
+    /// sequences, so the same number can name both a tab and a window.
+    TabId
+);
+define_id!(
+    /// The id kitty gives an OS window, the top level of the `ls` tree.
+    OsWindowId
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_and_conversions() {
+        assert_eq!(WindowId(7).to_string(), "7");
+        assert_eq!(TabId::from(3), TabId(3));
+        assert_eq!(u64::from(OsWindowId(9)), 9);
+    }
+
+    #[test]
+    fn test_serde_transparent() {
+        let id: WindowId = serde_json::from_value(serde_json::json!(5)).unwrap();
+        assert_eq!(id, WindowId(5));
+        assert_eq!(
+            serde_json::to_value(TabId(2)).unwrap(),
+            serde_json::json!(2)
+        );
+    }
+}
@@ -1,22 +1,35 @@
 pub mod client;
+pub mod color;
 pub mod command;
 pub mod commands;
 pub mod encryption;
 pub mod error;
+pub mod ids;
+pub mod match_spec;
 pub mod protocol;
 
-pub use client::{Kitty, KittyBuilder};
+pub use client::{
+    CaptureOptions, DEFAULT_PASSWORD_ENV, DEFAULT_POLL_INTERVAL, ExecTiming, ExitOutcome, Kitty,
+    KittyBuilder, PUBKEY_CACHE_TTL, RcStatus, SharedKitty, TEXT_SINK_CAPACITY, clear_pubkey_cache,
+    register_self_pubkey,
+};
+pub use color::Color;
 pub use commands::{
+    COMMANDS,
     // Tab commands
     CloseTabCommand,
     // Window commands
     CloseWindowCommand,
+    // Config commands
+    ConfigSummary,
     CreateMarkerCommand,
+    DebugConfigCommand,
     DetachTabCommand,
     DetachWindowCommand,
     // Process commands
     DisableLigaturesCommand,
     EnvCommand,
+    EnvMap,
     FocusTabCommand,
     FocusWindowCommand,
     // Style commands
@@ -27,9 +40,11 @@ pub use commands::{
     KittenCommand,
     LastUsedLayoutCommand,
     LaunchCommand,
+    LineEnding,
     LoadConfigCommand,
     // Special commands
     LsCommand,
+    NewTabCommand,
     NewWindowCommand,
     RemoveMarkerCommand,
     ResizeOSWindowCommand,
@@ -44,6 +59,7 @@ pub use commands::{
     SetColorsCommand,
     SetEnabledLayoutsCommand,
     SetFontSizeCommand,
+    SetOsWindowTitleCommand,
     SetSpacingCommand,
     SetTabColorCommand,
     SetTabTitleCommand,
@@ -53,7 +69,13 @@ pub use commands::{
     SignalChildCommand,
     action::*,
     process::ProcessInfo,
-    window::{OsInstance, TabInfo, WindowInfo, parse_response_data},
+    style::FontInfo,
+    window::{
+        OsInstance, SplitTree, TabInfo, WindowDiff, WindowInfo, active_window, all_windows,
+        all_windows_with_ids, diff_windows, parse_response_data, self_window,
+    },
 };
 pub use error::{CommandError, ConnectionError, EncryptionError, KittyError, ProtocolError};
-pub use protocol::{KittyMessage, KittyResponse};
+pub use ids::{OsWindowId, TabId, WindowId};
+pub use match_spec::{MATCH_KEYS, MatchSpec};
+pub use protocol::{KittyMessage, KittyResponse, ResponseKind};
@@ -1,10 +1,30 @@
-use clap::{Parser, Subcommand};
-use kitty_rc::{Kitty, KittyError};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use kitty_rc::{CommandError, Kitty, KittyBuilder, KittyError, WindowId};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "kitty-rc")]
 #[command(about = "Remote control utility for kitty terminal emulator", long_about = None)]
 struct Cli {
+    /// Path to the kitty socket; discovered from the usual locations if unset
+    #[arg(long, short, global = true)]
+    socket: Option<PathBuf>,
+
+    /// Remote control password, enabling encrypted commands
+    #[arg(long, global = true, conflicts_with = "password_file")]
+    password: Option<String>,
+
+    /// Read the remote control password from this file
+    #[arg(long, global = true)]
+    password_file: Option<PathBuf>,
+
+    /// kitty's public key (`1:<base85>`), instead of looking it up
+    #[arg(long, global = true)]
+    public_key: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -13,19 +33,52 @@ struct Cli {
 enum Commands {
     /// List all windows in kitty
     #[command(aliases = ["ls", "list"])]
-    ListWindows,
+    ListWindows {
+        /// Print the parsed window tree as JSON
+        #[arg(long)]
+        json: bool,
+    },
     /// Display the currently active window
     #[command(aliases = ["active", "a"])]
-    ActiveWindow,
+    ActiveWindow {
+        /// Print the window as JSON
+        #[arg(long)]
+        json: bool,
+    },
     /// Switch focus to a specific window
     #[command(aliases = ["g"])]
     Goto {
         /// Window ID to switch to
         window_id: u64,
     },
+    /// Type text into kitty windows
+    SendText {
+        /// Text to send, with \n, \t, \e and friends unescaped; `-` reads
+        /// it verbatim from stdin
+        text: String,
+        /// Send to the windows matching this spec instead of the active one
+        #[arg(long = "match", short = 'm')]
+        match_spec: Option<String>,
+        /// Send to all windows
+        #[arg(long)]
+        all: bool,
+        /// Skip the active window
+        #[arg(long)]
+        exclude_active: bool,
+    },
     /// Watch and print active window changes
     #[command(aliases = ["w"])]
-    Watch,
+    Watch {
+        /// Polling interval in milliseconds
+        #[arg(short, long, default_value_t = 250)]
+        interval: u64,
+    },
+    /// Print a shell completion script
+    #[command(hide = true)]
+    Completions {
+        /// Shell to generate completions for
+        shell: Shell,
+    },
 }
 
 #[tokio::main]
@@ -33,30 +86,102 @@ async fn main() -> Result<(), KittyError> {
     let cli = Cli::parse();
 
     match &cli.command {
-        Commands::ListWindows => {
-            handle_list_windows().await?;
+        Commands::ListWindows { json: true } => {
+            handle_list_windows_json(builder(&cli)).await?;
+        }
+        Commands::ListWindows { json: false } => {
+            handle_list_windows(builder(&cli)).await?;
         }
-        Commands::ActiveWindow => {
-            handle_active_window().await?;
+        Commands::ActiveWindow { json } => {
+            handle_active_window(builder(&cli), *json).await?;
         }
         Commands::Goto { window_id } => {
-            handle_goto(*window_id).await?;
+            handle_goto(builder(&cli), *window_id).await?;
+        }
+        Commands::SendText {
+            text,
+            match_spec,
+            all,
+            exclude_active,
+        } => {
+            let cmd = send_text_command(text, match_spec.as_deref(), *all, *exclude_active)?;
+            handle_send_text(builder(&cli), cmd).await?;
+        }
+        Commands::Watch { interval } => {
+            handle_watch(builder(&cli), Duration::from_millis(*interval)).await?;
         }
-        Commands::Watch => {
-            handle_watch().await?;
+        Commands::Completions { shell } => {
+            write_completions(*shell, &mut std::io::stdout());
         }
     }
 
     Ok(())
 }
 
-async fn handle_list_windows() -> Result<(), KittyError> {
-    println!("Connecting to kitty at ./kitty.socket...");
+/// A builder for the socket and credentials given on the command line.
+fn builder(cli: &Cli) -> KittyBuilder {
+    let mut builder = Kitty::builder().socket_path(resolve_socket(cli.socket.clone()));
+
+    if let Some(password) = &cli.password {
+        builder = builder.password(password);
+    }
+
+    if let Some(path) = &cli.password_file {
+        builder = builder.password_file(path);
+    }
+
+    if let Some(public_key) = &cli.public_key {
+        builder = builder.public_key(public_key);
+    }
+
+    builder
+}
+
+/// The socket given on the command line, else the first `*.sock` in
+/// `$XDG_RUNTIME_DIR/kitty`, `/run/user/<uid>/kitty` or `/tmp/kitty`, else
+/// `./kitty.socket`.
+fn resolve_socket(explicit: Option<PathBuf>) -> PathBuf {
+    if let Some(path) = explicit {
+        return path;
+    }
+
+    let mut dirs = Vec::new();
+    if let Ok(runtime) = std::env::var("XDG_RUNTIME_DIR") {
+        dirs.push(Path::new(&runtime).join("kitty"));
+    }
+    let uid = std::env::var("UID").unwrap_or_else(|_| "1000".to_string());
+    dirs.push(PathBuf::from(format!("/run/user/{}/kitty", uid)));
+    dirs.push(PathBuf::from("/tmp/kitty"));
+
+    dirs.iter()
+        .find_map(|dir| find_socket_in_dir(dir))
+        .unwrap_or_else(|| PathBuf::from("./kitty.socket"))
+}
+
+fn find_socket_in_dir(dir: &Path) -> Option<PathBuf> {
+    dir.read_dir()
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "sock"))
+}
+
+async fn handle_list_windows_json(builder: KittyBuilder) -> Result<(), KittyError> {
+    let mut kitty = builder.connect().await?;
 
-    let mut kitty = Kitty::builder()
-        .socket_path("./kitty.socket")
-        .connect()
-        .await?;
+    let cmd = kitty_rc::LsCommand::new().build()?;
+    let response = kitty.execute(&cmd).await?;
+    kitty.close().await?;
+
+    let instances =
+        kitty_rc::LsCommand::parse_response(&response).map_err(kitty_rc::ProtocolError::from)?;
+    print_json(&instances)
+}
+
+async fn handle_list_windows(builder: KittyBuilder) -> Result<(), KittyError> {
+    println!("Connecting to kitty...");
+
+    let mut kitty = builder.connect().await?;
 
     println!("Connected! Listing windows...\n");
 
@@ -181,17 +306,299 @@ async fn handle_list_windows() -> Result<(), KittyError> {
     Ok(())
 }
 
-async fn handle_active_window() -> Result<(), KittyError> {
-    println!("Active window command not yet implemented");
+async fn handle_active_window(builder: KittyBuilder, json: bool) -> Result<(), KittyError> {
+    let mut kitty = builder.connect().await?;
+
+    let cmd = kitty_rc::LsCommand::new().build()?;
+    let response = kitty.execute(&cmd).await?;
+    kitty.close().await?;
+
+    let instances =
+        kitty_rc::LsCommand::parse_response(&response).map_err(kitty_rc::ProtocolError::from)?;
+
+    let Some(window) = kitty_rc::active_window(&instances) else {
+        eprintln!("No active window found");
+        std::process::exit(1);
+    };
+
+    if json {
+        return print_json(window);
+    }
+
+    println!("Window ID: {}", format_id(window.id));
+    println!("Title: {}", window.title.as_deref().unwrap_or("-"));
+    println!("CWD: {}", window.cwd.as_deref().unwrap_or("-"));
+    println!("Shell PID: {}", format_id(window.pid));
+
     Ok(())
 }
 
-async fn handle_goto(window_id: u64) -> Result<(), KittyError> {
-    println!("Goto window {} command not yet implemented", window_id);
+async fn handle_goto(builder: KittyBuilder, window_id: u64) -> Result<(), KittyError> {
+    let mut kitty = builder.connect().await?;
+
+    let cmd = kitty_rc::FocusWindowCommand::new()
+        .match_spec(format!("id:{}", window_id))
+        .build()?;
+    let response = kitty.execute(&cmd).await?;
+    kitty.close().await?;
+
+    // kitty answers ok:false with "No matching windows" when the id is unknown
+    ensure_ok("focus-window", response)?;
+
+    println!("Focused window {}", window_id);
+    Ok(())
+}
+
+async fn handle_send_text(
+    builder: KittyBuilder,
+    cmd: kitty_rc::SendTextCommand,
+) -> Result<(), KittyError> {
+    let mut kitty = builder.connect().await?;
+
+    // Text read from stdin can exceed one chunk; build_all splits it into
+    // complete send-text commands that each keep the match and flags.
+    let mut response = None;
+    for message in cmd.build_all()? {
+        let reply = kitty.execute(&message).await?;
+        let failed = !reply.ok;
+        response = Some(reply);
+        if failed {
+            break;
+        }
+    }
+    kitty.close().await?;
+
+    match response {
+        Some(response) => ensure_ok("send-text", response),
+        None => Ok(()),
+    }
+}
+
+/// The `send-text` subcommand's command, with the text read and prefixed
+/// for kitty.
+fn send_text_command(
+    text: &str,
+    match_spec: Option<&str>,
+    all: bool,
+    exclude_active: bool,
+) -> Result<kitty_rc::SendTextCommand, KittyError> {
+    let mut cmd = kitty_rc::SendTextCommand::new(read_text(text)?)
+        .all(all)
+        .exclude_active(exclude_active);
+    if let Some(spec) = match_spec {
+        cmd = cmd.match_spec(spec);
+    }
+    Ok(cmd)
+}
+
+/// Turn a kitty-side failure into an error so the process exits non-zero.
+fn ensure_ok(cmd: &str, response: kitty_rc::KittyResponse) -> Result<(), KittyError> {
+    if response.ok {
+        return Ok(());
+    }
+
+    Err(CommandError::KittyError(cmd.to_string(), response.error.unwrap_or_default()).into())
+}
+
+/// The `send-text` data: stdin for `-`, otherwise the argument with
+/// backslash escapes applied, marked with kitty's `text:` encoding.
+fn read_text(text: &str) -> Result<String, KittyError> {
+    if text != "-" {
+        return Ok(format!("text:{}", unescape(text)));
+    }
+
+    let mut data = String::new();
+    std::io::stdin().read_to_string(&mut data)?;
+    Ok(format!("text:{}", data))
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('e') => out.push('\x1b'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+
+    out
+}
+
+async fn handle_watch(builder: KittyBuilder, interval: Duration) -> Result<(), KittyError> {
+    let mut kitty = builder.connect().await?;
+
+    tokio::select! {
+        result = watch_loop(&mut kitty, interval) => result?,
+        _ = tokio::signal::ctrl_c() => {}
+    }
+
+    kitty.close().await?;
     Ok(())
 }
 
-async fn handle_watch() -> Result<(), KittyError> {
-    println!("Watch command not yet implemented");
+/// Poll `ls` every `interval`, printing opened and closed windows and a line
+/// whenever the active window changes. Only returns on error.
+async fn watch_loop(kitty: &mut Kitty, interval: Duration) -> Result<(), KittyError> {
+    let mut previous: Option<Vec<kitty_rc::OsInstance>> = None;
+    let mut focused: Option<WindowId> = None;
+
+    loop {
+        let cmd = kitty_rc::LsCommand::new().build()?;
+        let response = kitty.execute(&cmd).await?;
+        let instances = kitty_rc::LsCommand::parse_response(&response)
+            .map_err(kitty_rc::ProtocolError::from)?;
+
+        if let Some(before) = &previous {
+            let diff = kitty_rc::diff_windows(before, &instances);
+
+            for id in &diff.opened {
+                println!("opened {}", id);
+            }
+
+            for id in &diff.closed {
+                println!("closed {}", id);
+            }
+        }
+
+        let active = kitty_rc::active_window(&instances);
+        let active_id = active.and_then(|window| window.id);
+
+        if previous.is_none() || active_id != focused {
+            let title = active.and_then(|window| window.title.as_deref());
+            println!("active {} {}", format_id(active_id), title.unwrap_or("-"));
+            focused = active_id;
+        }
+
+        previous = Some(instances);
+        tokio::time::sleep(interval).await;
+    }
+}
+
+fn print_json(value: &impl serde::Serialize) -> Result<(), KittyError> {
+    let json = serde_json::to_string_pretty(value).map_err(kitty_rc::ProtocolError::from)?;
+    println!("{}", json);
     Ok(())
 }
+
+fn format_id(id: Option<impl std::fmt::Display>) -> String {
+    id.map_or_else(|| "-".to_string(), |id| id.to_string())
+}
+
+fn write_completions(shell: Shell, out: &mut dyn std::io::Write) {
+    clap_complete::generate(shell, &mut Cli::command(), "kitty-rc", out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completions_for_each_shell() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let mut out = Vec::new();
+            write_completions(shell, &mut out);
+            let script = String::from_utf8(out).unwrap();
+            assert!(!script.is_empty(), "{shell} completions were empty");
+            assert!(script.contains("kitty-rc"));
+        }
+    }
+
+    #[test]
+    fn test_password_flags() {
+        let cli = Cli::try_parse_from([
+            "kitty-rc",
+            "ls",
+            "--password-file",
+            "/tmp/rc.password",
+            "--public-key",
+            "1:abc",
+        ])
+        .unwrap();
+        assert_eq!(cli.password_file, Some(PathBuf::from("/tmp/rc.password")));
+        assert_eq!(cli.public_key.as_deref(), Some("1:abc"));
+
+        let both = Cli::try_parse_from([
+            "kitty-rc",
+            "--password",
+            "secret",
+            "--password-file",
+            "/tmp/rc.password",
+            "ls",
+        ]);
+        assert!(both.is_err());
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r"echo hi\n"), "echo hi\n");
+        assert_eq!(unescape(r"a\tb\r\e[A"), "a\tb\r\x1b[A");
+        assert_eq!(unescape(r"C:\\dir \q\"), r"C:\dir \q\");
+    }
+
+    #[test]
+    fn test_send_text_payload() {
+        let cli = Cli::try_parse_from(["kitty-rc", "send-text", "--match", "id:3", r"echo hi\n"])
+            .unwrap();
+        let Commands::SendText {
+            text,
+            match_spec,
+            all,
+            exclude_active,
+        } = &cli.command
+        else {
+            panic!("expected send-text");
+        };
+
+        let msg = send_text_command(text, match_spec.as_deref(), *all, *exclude_active)
+            .unwrap()
+            .build()
+            .unwrap();
+        let payload = msg.payload.unwrap();
+        assert_eq!(payload["data"], "text:echo hi\n");
+        assert_eq!(payload["match"], "id:3");
+    }
+
+    #[test]
+    fn test_send_text_large_payload() {
+        let cmd = send_text_command(&"x".repeat(9000), Some("id:3"), false, true).unwrap();
+        let messages = cmd.build_all().unwrap();
+        assert_eq!(messages.len(), 3);
+        for msg in messages {
+            let payload = msg.payload.unwrap();
+            assert!(payload["data"].as_str().unwrap().starts_with("text:"));
+            assert_eq!(payload["match"], "id:3");
+            assert_eq!(payload["exclude_active"], true);
+        }
+    }
+
+    #[test]
+    fn test_resolve_socket() {
+        let explicit = PathBuf::from("/somewhere/kitty-1.sock");
+        assert_eq!(resolve_socket(Some(explicit.clone())), explicit);
+
+        let dir = std::env::temp_dir().join(format!("kitty-rc-discover-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(find_socket_in_dir(&dir), None);
+
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+        std::fs::write(dir.join("kitty-42.sock"), "").unwrap();
+        assert_eq!(find_socket_in_dir(&dir), Some(dir.join("kitty-42.sock")));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,723 @@
+use crate::commands::window::WindowInfo;
+use crate::error::CommandError;
+use crate::ids::{TabId, WindowId};
+use regex::Regex;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+/// Field names kitty understands in a window match expression.
+pub const MATCH_KEYS: &[&str] = &[
+    "id", "title", "pid", "cwd", "cmdline", "num", "env", "var", "state", "recent", "neighbor",
+];
+
+/// A kitty window match expression, as accepted by the `match` field of
+/// most remote control commands (e.g. `id:1` or `recent:0`).
+///
+/// Plain strings convert into a `MatchSpec` unchanged, so every builder's
+/// `match_spec` setter accepts both `"id:1"` and `MatchSpec::id(1)`. To
+/// catch typos such as `matsh:id1` before kitty silently matches nothing,
+/// go through [`MatchSpec::parse`] (or `str::parse`) instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchSpec {
+    expr: String,
+    /// The boolean operator joining the top level of `expr`, if it was built
+    /// with [`and`](Self::and) or [`or`](Self::or). `Some("")` marks a
+    /// parsed or raw compound expression, which is always parenthesized.
+    op: Option<&'static str>,
+}
+
+impl MatchSpec {
+    fn field(expr: String) -> Self {
+        Self { expr, op: None }
+    }
+
+    /// An unchecked expression from a plain string. Anything but a single
+    /// term gets parenthesized when combined, since its operators are
+    /// unknown; text that does not parse counts as a single term only if
+    /// it has no whitespace.
+    fn raw(expr: String) -> Self {
+        let single = match parse_expr(&expr) {
+            Ok(parsed) => parsed.terms().len() <= 1,
+            Err(_) => !expr.trim().contains(char::is_whitespace),
+        };
+        Self {
+            expr,
+            op: (!single).then_some(""),
+        }
+    }
+
+    /// Match the window with kitty id `id`. Plain `u64`s convert, a
+    /// [`TabId`] does not; use [`tab_id`](Self::tab_id) for tab matches.
+    ///
+    /// ```compile_fail
+    /// use kitty_rc::{MatchSpec, TabId};
+    ///
+    /// let spec = MatchSpec::id(TabId(3));
+    /// ```
+    pub fn id(id: impl Into<WindowId>) -> Self {
+        Self::field(format!("id:{}", id.into()))
+    }
+
+    /// Match the tab with kitty id `id`, for tab commands' `match` and
+    /// `match_tab` fields.
+    pub fn tab_id(id: impl Into<TabId>) -> Self {
+        Self::field(format!("id:{}", id.into()))
+    }
+
+    /// Match the window whose shell process has `pid`.
+    pub fn pid(pid: u32) -> Self {
+        Self::field(format!("pid:{}", pid))
+    }
+
+    /// Match the window at position `n` in its tab, counting from 1.
+    /// Zero and negative values count back from the last window.
+    pub fn num(n: i32) -> Self {
+        Self::field(format!("num:{}", n))
+    }
+
+    /// Match windows in a state such as `active`, `focused`, `self`,
+    /// `needs_attention` or `parent_active`.
+    pub fn state(state: impl AsRef<str>) -> Self {
+        Self::field(format!("state:{}", state.as_ref()))
+    }
+
+    /// Match windows that have user variable `name` set to a value matching
+    /// the regex `pattern`, quoted like [`title`](Self::title).
+    pub fn var(name: impl AsRef<str>, pattern: impl AsRef<str>) -> Self {
+        Self::field(format!(
+            "var:{}",
+            quote(&format!("{}={}", name.as_ref(), pattern.as_ref()))
+        ))
+    }
+
+    /// Match the neighbor of the active window in direction `direction`:
+    /// `left`, `right`, `top` or `bottom`.
+    pub fn neighbor(direction: impl AsRef<str>) -> Self {
+        Self::field(format!("neighbor:{}", direction.as_ref()))
+    }
+
+    /// Match the `n`th most recently active window. `recent:0` is the
+    /// active window, `recent:1` the one active before it, and so on.
+    pub fn recent(n: u32) -> Self {
+        Self::field(format!("recent:{}", n))
+    }
+
+    /// Match windows whose title matches the regex `pattern`. Patterns
+    /// containing whitespace, parentheses, quotes, `:` or `,` are wrapped in
+    /// kitty's `"..."` quoting so they aren't split by the match grammar;
+    /// other regex syntax, including `^`/`$` anchors, is passed through
+    /// untouched.
+    pub fn title(pattern: impl AsRef<str>) -> Self {
+        Self::field(format!("title:{}", quote(pattern.as_ref())))
+    }
+
+    /// Match windows whose working directory matches the regex `pattern`,
+    /// quoted like [`title`](Self::title).
+    pub fn cwd(pattern: impl AsRef<str>) -> Self {
+        Self::field(format!("cwd:{}", quote(pattern.as_ref())))
+    }
+
+    /// Match windows with environment variable `name` set to a value
+    /// matching the regex `pattern`, quoted like [`title`](Self::title).
+    pub fn env(name: impl AsRef<str>, pattern: impl AsRef<str>) -> Self {
+        Self::field(format!(
+            "env:{}",
+            quote(&format!("{}={}", name.as_ref(), pattern.as_ref()))
+        ))
+    }
+
+    /// Windows matching both `self` and `other`.
+    pub fn and(self, other: impl Into<MatchSpec>) -> Self {
+        self.combine("and", other.into())
+    }
+
+    /// Windows matching either `self` or `other`.
+    pub fn or(self, other: impl Into<MatchSpec>) -> Self {
+        self.combine("or", other.into())
+    }
+
+    /// Join two expressions with `op`, parenthesizing either side that was
+    /// itself joined with the other operator so precedence is explicit.
+    fn combine(self, op: &'static str, other: MatchSpec) -> Self {
+        Self {
+            expr: format!("{} {} {}", self.operand(op), op, other.operand(op)),
+            op: Some(op),
+        }
+    }
+
+    fn operand(self, op: &'static str) -> String {
+        match self.op {
+            Some(inner) if inner != op => format!("({})", self.expr),
+            _ => self.expr,
+        }
+    }
+
+    /// Check `expr` against kitty's match grammar: every term must be
+    /// `key:value` with a key from [`MATCH_KEYS`], joined by `and`, `or`,
+    /// `not` and parentheses. Fails with
+    /// [`CommandError::InvalidWindowMatch`] otherwise.
+    pub fn parse(expr: &str) -> Result<Self, CommandError> {
+        let terms = parse_expr(expr)?.terms().len();
+        Ok(Self {
+            expr: expr.to_string(),
+            // A parsed compound expression gets parenthesized whenever it
+            // is combined further, whatever operators it contains.
+            op: (terms > 1).then_some(""),
+        })
+    }
+
+    /// Evaluate the expression against a window from a parsed `ls`
+    /// snapshot, without asking kitty.
+    ///
+    /// Supports `id:`, `pid:` (the window's process or any foreground
+    /// process), `title:` and `cwd:` (regex search), and `env:`/`var:`
+    /// (`NAME=regex`, or just `NAME` to test that it is set), combined with
+    /// `and`, `or`, `not` and parentheses. `recent:`, `num:`, `state:`,
+    /// `neighbor:` and `cmdline:` depend on state only kitty has, so
+    /// expressions using them fail with
+    /// [`CommandError::InvalidWindowMatch`] rather than quietly not
+    /// matching, as do malformed expressions and invalid regexes.
+    pub fn matches(&self, window: &WindowInfo) -> Result<bool, CommandError> {
+        let parsed = parse_expr(&self.expr)?;
+        if let Some((key, _)) = parsed
+            .terms()
+            .into_iter()
+            .find(|(key, _)| !OFFLINE_KEYS.contains(key))
+        {
+            return Err(invalid(
+                &self.expr,
+                format!("'{}' can only be evaluated by kitty", key),
+            ));
+        }
+        parsed
+            .eval(window)
+            .map_err(|reason| invalid(&self.expr, reason))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.expr
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term(String, String),
+}
+
+/// A parsed match expression.
+#[derive(Debug)]
+enum Expr {
+    Term(String, String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+/// Keys [`MatchSpec::matches`] can evaluate from an `ls` snapshot alone.
+const OFFLINE_KEYS: &[&str] = &["id", "pid", "title", "cwd", "env", "var"];
+
+impl Expr {
+    fn terms(&self) -> Vec<(&str, &str)> {
+        match self {
+            Expr::Term(key, value) => vec![(key, value)],
+            Expr::Not(inner) => inner.terms(),
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                let mut terms = a.terms();
+                terms.extend(b.terms());
+                terms
+            }
+        }
+    }
+
+    fn eval(&self, window: &WindowInfo) -> Result<bool, String> {
+        Ok(match self {
+            Expr::Term(key, value) => term_matches(key, value, window)?,
+            Expr::Not(inner) => !inner.eval(window)?,
+            Expr::And(a, b) => a.eval(window)? && b.eval(window)?,
+            Expr::Or(a, b) => a.eval(window)? || b.eval(window)?,
+        })
+    }
+}
+
+fn term_matches(key: &str, value: &str, window: &WindowInfo) -> Result<bool, String> {
+    let regex =
+        |pattern: &str| Regex::new(pattern).map_err(|e| format!("invalid {} pattern: {}", key, e));
+    let number = || {
+        value
+            .parse::<u64>()
+            .map_err(|_| format!("{} expects a number, got '{}'", key, value))
+    };
+
+    match key {
+        "id" => Ok(window.id == Some(WindowId(number()?))),
+        "pid" => {
+            let pid = Some(number()?);
+            Ok(window.pid == pid
+                || window
+                    .foreground_processes
+                    .iter()
+                    .any(|process| process.pid == pid))
+        }
+        "title" | "cwd" => {
+            let field = if key == "title" {
+                &window.title
+            } else {
+                &window.cwd
+            };
+            let regex = regex(value)?;
+            Ok(field.as_deref().is_some_and(|text| regex.is_match(text)))
+        }
+        "env" | "var" => {
+            let vars = if key == "env" {
+                &window.env
+            } else {
+                &window.user_vars
+            };
+            match value.split_once('=') {
+                Some((name, pattern)) => {
+                    let regex = regex(pattern)?;
+                    Ok(vars.get(name).is_some_and(|text| regex.is_match(text)))
+                }
+                None => Ok(vars.contains_key(value)),
+            }
+        }
+        _ => Err(format!("'{}' can only be evaluated by kitty", key)),
+    }
+}
+
+fn invalid(expr: &str, reason: impl fmt::Display) -> CommandError {
+    CommandError::InvalidWindowMatch(format!("{}: {}", expr, reason))
+}
+
+/// Split a match expression into tokens, checking every term's key
+/// against [`MATCH_KEYS`] and unescaping quoted values.
+fn tokenize(expr: &str) -> Result<Vec<Token>, CommandError> {
+    let mut chars = expr.chars().peekable();
+    let mut tokens = Vec::new();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            chars.next();
+            match c {
+                '(' => tokens.push(Token::Open),
+                ')' => tokens.push(Token::Close),
+                _ => {}
+            }
+            continue;
+        }
+
+        let mut word = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == '(' || c == ')' {
+                break;
+            }
+            chars.next();
+            word.push(c);
+            if c == ':' {
+                break;
+            }
+        }
+        match word.as_str() {
+            "and" => tokens.push(Token::And),
+            "or" => tokens.push(Token::Or),
+            "not" => tokens.push(Token::Not),
+            _ => {
+                let Some(key) = word.strip_suffix(':') else {
+                    return Err(invalid(expr, format!("expected key:value, got '{}'", word)));
+                };
+                if !MATCH_KEYS.contains(&key) {
+                    return Err(invalid(expr, format!("unknown match key '{}'", key)));
+                }
+                let value = term_value(expr, key, &mut chars)?;
+                tokens.push(Token::Term(key.to_string(), value));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn term_value(
+    expr: &str,
+    key: &str,
+    chars: &mut Peekable<Chars<'_>>,
+) -> Result<String, CommandError> {
+    let mut value = String::new();
+    if chars.next_if_eq(&'"').is_some() {
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.next_if_eq(&'"').is_some() => value.push('"'),
+                '"' => return Ok(value),
+                c => value.push(c),
+            }
+        }
+        return Err(invalid(expr, "unterminated quote"));
+    }
+
+    while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ')') {
+        value.push(c);
+    }
+    if value.is_empty() {
+        return Err(invalid(expr, format!("missing value for '{}'", key)));
+    }
+    Ok(value)
+}
+
+/// Recursive-descent parser over [`tokenize`]'s output. `not` binds
+/// tightest, then `and` (implied between adjacent terms), then `or`.
+struct Parser<'a> {
+    expr: &'a str,
+    tokens: Peekable<std::vec::IntoIter<Token>>,
+}
+
+impl Parser<'_> {
+    fn or(&mut self) -> Result<Expr, CommandError> {
+        let mut left = self.and()?;
+        while self.tokens.next_if_eq(&Token::Or).is_some() {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, CommandError> {
+        let mut left = self.unary()?;
+        loop {
+            match self.tokens.peek() {
+                Some(Token::And) => {
+                    self.tokens.next();
+                }
+                Some(Token::Open | Token::Not | Token::Term(..)) => {}
+                _ => return Ok(left),
+            }
+            left = Expr::And(Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, CommandError> {
+        match self.tokens.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let inner = self.or()?;
+                match self.tokens.next() {
+                    Some(Token::Close) => Ok(inner),
+                    _ => Err(invalid(self.expr, "unbalanced parentheses")),
+                }
+            }
+            Some(Token::Term(key, value)) => Ok(Expr::Term(key, value)),
+            _ => Err(invalid(self.expr, "expected a key:value term")),
+        }
+    }
+}
+
+/// Parse a match expression, rejecting unknown keys and malformed syntax.
+fn parse_expr(expr: &str) -> Result<Expr, CommandError> {
+    let mut parser = Parser {
+        expr,
+        tokens: tokenize(expr)?.into_iter().peekable(),
+    };
+    let parsed = parser.or()?;
+    if parser.tokens.next().is_some() {
+        return Err(invalid(expr, "unbalanced parentheses"));
+    }
+    Ok(parsed)
+}
+
+/// Quote `value` for kitty's match grammar if it would otherwise be split
+/// into several tokens. Inside quotes kitty only unescapes `\"`.
+fn quote(value: &str) -> String {
+    if value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '(' | ')' | '"' | ':' | ','))
+    {
+        format!("\"{}\"", value.replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for MatchSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expr)
+    }
+}
+
+impl FromStr for MatchSpec {
+    type Err = CommandError;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        Self::parse(expr)
+    }
+}
+
+impl From<MatchSpec> for String {
+    fn from(spec: MatchSpec) -> Self {
+        spec.expr
+    }
+}
+
+impl From<String> for MatchSpec {
+    fn from(expr: String) -> Self {
+        Self::raw(expr)
+    }
+}
+
+impl From<&String> for MatchSpec {
+    fn from(expr: &String) -> Self {
+        Self::raw(expr.clone())
+    }
+}
+
+impl From<&str> for MatchSpec {
+    fn from(expr: &str) -> Self {
+        Self::raw(expr.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent() {
+        assert_eq!(MatchSpec::recent(0).to_string(), "recent:0");
+        assert_eq!(MatchSpec::recent(3).as_str(), "recent:3");
+    }
+
+    #[test]
+    fn test_title() {
+        assert_eq!(MatchSpec::title("^vim$").as_str(), "title:^vim$");
+        assert_eq!(MatchSpec::title(r"v\d+.*").as_str(), r"title:v\d+.*");
+        assert_eq!(
+            MatchSpec::title("my (dev) shell").as_str(),
+            r#"title:"my (dev) shell""#
+        );
+        assert_eq!(
+            MatchSpec::title(r#"say "hi""#).as_str(),
+            r#"title:"say \"hi\"""#
+        );
+        assert_eq!(MatchSpec::title("").as_str(), r#"title:"""#);
+    }
+
+    #[test]
+    fn test_title_separators_and_anchors() {
+        assert_eq!(MatchSpec::title("foo:bar").as_str(), r#"title:"foo:bar""#);
+        assert_eq!(MatchSpec::title("a,b").as_str(), r#"title:"a,b""#);
+        assert_eq!(
+            MatchSpec::title("^vim:main$").as_str(),
+            r#"title:"^vim:main$""#
+        );
+        assert_eq!(MatchSpec::title("^$").as_str(), "title:^$");
+    }
+
+    #[test]
+    fn test_cwd_and_env() {
+        assert_eq!(MatchSpec::cwd("^/home/me$").as_str(), "cwd:^/home/me$");
+        assert_eq!(
+            MatchSpec::cwd("/srv/my project").as_str(),
+            r#"cwd:"/srv/my project""#
+        );
+        assert_eq!(MatchSpec::env("TERM", "^xterm").as_str(), "env:TERM=^xterm");
+        assert_eq!(
+            MatchSpec::env("PATH", "/usr/bin:/bin").as_str(),
+            r#"env:"PATH=/usr/bin:/bin""#
+        );
+    }
+
+    #[test]
+    fn test_into_string() {
+        let spec: String = MatchSpec::recent(1).into();
+        assert_eq!(spec, "recent:1");
+    }
+
+    #[test]
+    fn test_simple_fields() {
+        assert_eq!(MatchSpec::id(7).as_str(), "id:7");
+        assert_eq!(MatchSpec::id(WindowId(7)), MatchSpec::id(7));
+        assert_eq!(MatchSpec::tab_id(TabId(2)).as_str(), "id:2");
+        assert_eq!(MatchSpec::pid(1234).as_str(), "pid:1234");
+        assert_eq!(MatchSpec::num(-1).as_str(), "num:-1");
+        assert_eq!(MatchSpec::state("focused").as_str(), "state:focused");
+        assert_eq!(MatchSpec::neighbor("left").as_str(), "neighbor:left");
+        assert_eq!(
+            MatchSpec::var("project", "^web$").as_str(),
+            "var:project=^web$"
+        );
+        assert_eq!(MatchSpec::var("note", "a b").as_str(), r#"var:"note=a b""#);
+    }
+
+    #[test]
+    fn test_and_or() {
+        let spec = MatchSpec::title("vim").and(MatchSpec::state("active"));
+        assert_eq!(spec.as_str(), "title:vim and state:active");
+
+        let spec = MatchSpec::id(1).or(MatchSpec::id(2)).or("id:3");
+        assert_eq!(spec.as_str(), "id:1 or id:2 or id:3");
+
+        let spec = MatchSpec::id(1)
+            .or(MatchSpec::id(2))
+            .and(MatchSpec::cwd("/srv"));
+        assert_eq!(spec.as_str(), "(id:1 or id:2) and cwd:/srv");
+
+        let spec = MatchSpec::pid(9).or(MatchSpec::title("a").and(MatchSpec::num(1)));
+        assert_eq!(spec.as_str(), "pid:9 or (title:a and num:1)");
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(MatchSpec::from("id:1"), MatchSpec::id(1));
+        assert_eq!(
+            MatchSpec::from(String::from("recent:0")),
+            MatchSpec::recent(0)
+        );
+    }
+
+    #[test]
+    fn test_parse_valid() {
+        for expr in [
+            "id:1",
+            "title:^vim$",
+            "state:focused and not cwd:/tmp",
+            "(id:1 or id:2) and var:project=web",
+            r#"title:"my (dev) shell""#,
+            r#"title:"say \"hi\"" or pid:9"#,
+            "neighbor:left",
+        ] {
+            assert_eq!(MatchSpec::parse(expr).unwrap().as_str(), expr);
+        }
+        assert_eq!(
+            "recent:0".parse::<MatchSpec>().unwrap(),
+            MatchSpec::recent(0)
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for expr in [
+            "",
+            "matsh:id1",
+            "id1",
+            "id:",
+            "title:vim and",
+            r#"title:"unterminated"#,
+            "id:1 or bogus:2",
+            "and id:1",
+            "(id:1",
+            "id:1)",
+        ] {
+            assert!(
+                matches!(
+                    MatchSpec::parse(expr),
+                    Err(CommandError::InvalidWindowMatch(_))
+                ),
+                "{:?} should be rejected",
+                expr
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_combines_with_parens() {
+        let spec = MatchSpec::parse("id:1 or id:2")
+            .unwrap()
+            .and(MatchSpec::pid(3));
+        assert_eq!(spec.as_str(), "(id:1 or id:2) and pid:3");
+        let spec = MatchSpec::parse("id:1").unwrap().or(MatchSpec::pid(3));
+        assert_eq!(spec.as_str(), "id:1 or pid:3");
+    }
+
+    #[test]
+    fn test_raw_strings_combine_with_parens() {
+        let spec = MatchSpec::from("id:1 or id:2").and(MatchSpec::pid(3));
+        assert_eq!(spec.as_str(), "(id:1 or id:2) and pid:3");
+
+        let spec = MatchSpec::id(1).and("id:2 or id:3");
+        assert_eq!(spec.as_str(), "id:1 and (id:2 or id:3)");
+
+        let spec = MatchSpec::id(1).and(String::from("bogus stuff"));
+        assert_eq!(spec.as_str(), "id:1 and (bogus stuff)");
+
+        let spec = MatchSpec::id(1).or("id:2");
+        assert_eq!(spec.as_str(), "id:1 or id:2");
+    }
+
+    fn window() -> WindowInfo {
+        serde_json::from_value(serde_json::json!({
+            "id": 7,
+            "pid": 100,
+            "title": "vim: main.rs",
+            "cwd": "/home/me/src/kitty-rc",
+            "env": {"TERM": "xterm-kitty"},
+            "user_vars": {"project": "web"},
+            "foreground_processes": [{"pid": 100}, {"pid": 200}]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_matches() {
+        let window = window();
+        let matches = |spec: MatchSpec| spec.matches(&window).unwrap();
+
+        assert!(matches(MatchSpec::id(7)));
+        assert!(!matches(MatchSpec::id(8)));
+        assert!(matches(MatchSpec::pid(100)));
+        assert!(matches(MatchSpec::pid(200)));
+        assert!(!matches(MatchSpec::pid(300)));
+        assert!(matches(MatchSpec::title("^vim")));
+        assert!(matches(MatchSpec::title("main.rs")));
+        assert!(!matches(MatchSpec::title("^main")));
+        assert!(matches(MatchSpec::cwd("kitty-rc$")));
+        assert!(matches(MatchSpec::env("TERM", "kitty")));
+        assert!(!matches(MatchSpec::env("TERM", "^screen")));
+        assert!(!matches(MatchSpec::env("HOME", ".")));
+        assert!(matches(MatchSpec::var("project", "^web$")));
+        assert!(matches("var:project".into()));
+        assert!(!matches("var:branch".into()));
+    }
+
+    #[test]
+    fn test_matches_combinators() {
+        let window = window();
+        let matches = |expr: &str| MatchSpec::from(expr).matches(&window).unwrap();
+
+        assert!(matches("id:7 and title:vim"));
+        assert!(!matches("id:7 and title:emacs"));
+        assert!(matches("id:8 or title:vim"));
+        assert!(matches("not id:8"));
+        assert!(matches("id:7 title:vim"));
+        assert!(!matches("not (id:7 or id:8)"));
+        assert!(matches("id:1 or id:7 and pid:100"));
+        assert!(!matches("(id:1 or id:7) and pid:300"));
+        assert!(matches(r#"title:"vim: main""#));
+    }
+
+    #[test]
+    fn test_matches_errors() {
+        let window = window();
+        for expr in [
+            "recent:0",
+            "id:8 and state:focused",
+            "num:1",
+            "neighbor:left",
+            "id:x",
+            "title:(",
+            "matsh:id1",
+        ] {
+            assert!(
+                matches!(
+                    MatchSpec::from(expr).matches(&window),
+                    Err(CommandError::InvalidWindowMatch(_))
+                ),
+                "{:?} should fail",
+                expr
+            );
+        }
+    }
+}
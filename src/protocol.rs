@@ -1,4 +1,5 @@
 use crate::error::ProtocolError;
+use base64::Engine;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicU32, Ordering};
 
@@ -26,6 +27,14 @@ pub struct KittyMessage {
     pub stream_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
+    /// Client-side guard checked by every `Kitty` send path, never sent
+    /// to kitty.
+    #[serde(skip)]
+    pub only_at_prompt: bool,
+    /// Set on commands that wait for the user, which `Kitty` reads with
+    /// its interactive timeout instead of the default one. Never sent.
+    #[serde(skip)]
+    pub interactive: bool,
 }
 
 impl KittyMessage {
@@ -40,6 +49,8 @@ impl KittyMessage {
             cancel_async: None,
             stream_id: None,
             stream: None,
+            only_at_prompt: false,
+            interactive: false,
         }
     }
 
@@ -48,6 +59,16 @@ impl KittyMessage {
         self
     }
 
+    pub fn only_at_prompt(mut self, value: bool) -> Self {
+        self.only_at_prompt = value;
+        self
+    }
+
+    pub fn interactive(mut self, value: bool) -> Self {
+        self.interactive = value;
+        self
+    }
+
     pub fn kitty_window_id(mut self, id: impl Into<String>) -> Self {
         self.kitty_window_id = Some(id.into());
         self
@@ -98,6 +119,12 @@ impl KittyMessage {
         false
     }
 
+    /// Split a message whose payload has a string over 4 KiB into
+    /// `stream` chunks of that field, ending with an empty chunk. kitty's
+    /// remote control protocol has no compression marker and its commands
+    /// take `data` only as plain base64 or text, so chunks carry the data
+    /// as given. `send-text` has no stream and is split into complete
+    /// commands instead, see [`splits_into_commands`](Self::splits_into_commands).
     pub fn into_chunks(mut self) -> Vec<KittyMessage> {
         let mut chunks = Vec::new();
 
@@ -105,50 +132,105 @@ impl KittyMessage {
             return vec![self];
         }
 
-        if let Some(payload) = self.payload.take() {
-            if let Some(obj) = payload.as_object() {
-                let stream_id = Self::generate_unique_id();
+        if self.splits_into_commands() {
+            return self.into_send_text_commands();
+        }
 
-                for (_key, value) in obj {
-                    if let Some(s) = value.as_str() {
-                        if s.len() > MAX_CHUNK_SIZE {
-                            for (i, chunk_data) in s.as_bytes().chunks(MAX_CHUNK_SIZE).enumerate() {
-                                let mut chunk_msg = self.clone();
-                                chunk_msg.stream_id = Some(stream_id.clone());
-                                chunk_msg.stream = Some(true);
-
-                                let mut chunk_payload = serde_json::Map::new();
-                                chunk_payload.insert(
-                                    "data".to_string(),
-                                    serde_json::Value::String(
-                                        String::from_utf8_lossy(chunk_data).to_string(),
-                                    ),
-                                );
-                                chunk_payload.insert("chunk_num".to_string(), serde_json::json!(i));
-                                chunk_msg.payload = Some(serde_json::Value::Object(chunk_payload));
-
-                                chunks.push(chunk_msg);
-                            }
-
-                            let mut end_chunk = self.clone();
-                            end_chunk.stream_id = Some(stream_id);
-                            end_chunk.stream = Some(true);
-                            let mut end_payload = serde_json::Map::new();
-                            end_payload.insert(
-                                "data".to_string(),
-                                serde_json::Value::String(String::new()),
-                            );
-                            end_chunk.payload = Some(serde_json::Value::Object(end_payload));
-                            chunks.push(end_chunk);
-
-                            return chunks;
-                        }
-                    }
-                }
+        let Some(serde_json::Value::Object(mut payload)) = self.payload.take() else {
+            return vec![self];
+        };
+        let Some(key) = payload
+            .iter()
+            .find(|(_, value)| value.as_str().is_some_and(|s| s.len() > MAX_CHUNK_SIZE))
+            .map(|(key, _)| key.clone())
+        else {
+            self.payload = Some(serde_json::Value::Object(payload));
+            return vec![self];
+        };
+        let Some(serde_json::Value::String(data)) = payload.remove(&key) else {
+            unreachable!("the oversized field is a string");
+        };
+
+        // Every chunk carries the rest of the payload (match, flags), which
+        // kitty reads from each chunk, with its share of the field.
+        let stream_id = Self::generate_unique_id();
+        let chunk = |piece: &str, num: Option<usize>| {
+            let mut chunk_payload = payload.clone();
+            chunk_payload.insert(key.clone(), serde_json::Value::String(piece.to_string()));
+            if let Some(num) = num {
+                chunk_payload.insert("chunk_num".to_string(), serde_json::json!(num));
+            }
+            let mut chunk_msg = self.clone();
+            chunk_msg.stream_id = Some(stream_id.clone());
+            chunk_msg.stream = Some(true);
+            chunk_msg.payload = Some(serde_json::Value::Object(chunk_payload));
+            chunk_msg
+        };
+
+        let mut rest = data.as_str();
+        while !rest.is_empty() {
+            let mut end = MAX_CHUNK_SIZE.min(rest.len());
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            let (piece, tail) = rest.split_at(end);
+            chunks.push(chunk(piece, Some(chunks.len())));
+            rest = tail;
+        }
+        chunks.push(chunk("", None));
+
+        chunks
+    }
+
+    /// Whether [`into_chunks`](Self::into_chunks) splits this message into
+    /// independent commands, each answered by kitty, rather than one
+    /// stream answered once. True for `send-text`.
+    pub fn splits_into_commands(&self) -> bool {
+        self.cmd == "send-text"
+    }
+
+    /// Split an oversized `send-text` into complete `send-text` commands.
+    /// Each keeps the whole payload (match, flags) and repeats the `data`
+    /// encoding prefix, so kitty decodes every piece on its own. Text is
+    /// split on char boundaries, base64 and base85 on whole groups.
+    fn into_send_text_commands(mut self) -> Vec<KittyMessage> {
+        let Some(serde_json::Value::Object(mut payload)) = self.payload.take() else {
+            return vec![self];
+        };
+        let Some(serde_json::Value::String(data)) = payload.remove("data") else {
+            self.payload = Some(serde_json::Value::Object(payload));
+            return vec![self];
+        };
+
+        let (prefix, group) = match data.split_once(':') {
+            Some(("base64", _)) => ("base64:", 4),
+            Some(("base85", _)) => ("base85:", 5),
+            Some(("text", _)) => ("text:", 1),
+            _ => ("", 1),
+        };
+        let limit = MAX_CHUNK_SIZE - prefix.len();
+        let limit = limit - limit % group;
+
+        let mut chunks = Vec::new();
+        let mut rest = &data[prefix.len()..];
+        while !rest.is_empty() {
+            let mut end = limit.min(rest.len());
+            while !rest.is_char_boundary(end) {
+                end -= 1;
             }
+            let (piece, tail) = rest.split_at(end);
+
+            let mut chunk_payload = payload.clone();
+            chunk_payload.insert(
+                "data".to_string(),
+                serde_json::Value::String(format!("{}{}", prefix, piece)),
+            );
+            let mut chunk = self.clone();
+            chunk.payload = Some(serde_json::Value::Object(chunk_payload));
+            chunks.push(chunk);
+            rest = tail;
         }
 
-        chunks.push(self);
         chunks
     }
 
@@ -158,6 +240,15 @@ impl KittyMessage {
         Ok(message.into_bytes())
     }
 
+    /// Like [`encode`](Self::encode) but with indented JSON, for inspecting
+    /// the wire format while debugging. kitty tolerates whitespace in the
+    /// JSON body, but use `encode` for real traffic.
+    pub fn encode_pretty(&self) -> Result<Vec<u8>, ProtocolError> {
+        let json = serde_json::to_string_pretty(self)?;
+        let message = format!("{}{}{}", PREFIX, json, SUFFIX);
+        Ok(message.into_bytes())
+    }
+
     pub fn decode(data: &[u8]) -> Result<Self, ProtocolError> {
         let s = std::str::from_utf8(data)
             .map_err(|e| ProtocolError::InvalidMessageFormat(e.to_string()))?;
@@ -178,6 +269,42 @@ impl KittyMessage {
     }
 }
 
+/// What a response envelope is, judged from its fields by
+/// [`ResponseKind::classify`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseKind {
+    /// Interim status while an async command runs: the envelope carries a
+    /// `progress` field. More envelopes follow.
+    Progress,
+    /// The result of the command. Every envelope that is neither progress
+    /// nor an ack.
+    Final,
+    /// `ok` with neither `data` nor `error`, tagged with `async_id` or
+    /// `stream: true`: kitty accepted an async or streamed request and the
+    /// result follows in a later envelope.
+    Ack,
+}
+
+impl ResponseKind {
+    pub fn classify(envelope: &serde_json::Map<String, serde_json::Value>) -> Self {
+        if envelope.contains_key("progress") {
+            return ResponseKind::Progress;
+        }
+
+        let ok = envelope.get("ok").and_then(|v| v.as_bool()) == Some(true);
+        let has_result = !envelope.get("data").is_none_or(|v| v.is_null())
+            || !envelope.get("error").is_none_or(|v| v.is_null());
+        let pending = envelope.contains_key("async_id")
+            || envelope.get("stream").and_then(|v| v.as_bool()) == Some(true);
+
+        if ok && !has_result && pending {
+            ResponseKind::Ack
+        } else {
+            ResponseKind::Final
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KittyResponse {
     pub ok: bool,
@@ -186,7 +313,42 @@ pub struct KittyResponse {
 }
 
 impl KittyResponse {
+    /// Whether `data` holds a whole response envelope: the full prefix, a
+    /// body and the terminator. JSON escapes control characters, so the
+    /// terminator cannot occur inside the body. Data that can no longer
+    /// grow into a valid prefix also counts as complete, letting
+    /// [`decode`](Self::decode) report it instead of reading on.
+    pub(crate) fn is_complete(data: &[u8]) -> bool {
+        let prefix = PREFIX.as_bytes();
+        let overlap = data.len().min(prefix.len());
+        if data[..overlap] != prefix[..overlap] {
+            return true;
+        }
+
+        data.len() >= prefix.len() + SUFFIX.len() && data.ends_with(SUFFIX.as_bytes())
+    }
+
+    /// Length of the first whole envelope at the start of `data`, if one
+    /// has fully arrived. Lets several envelopes sent back to back be
+    /// split apart.
+    pub(crate) fn frame_len(data: &[u8]) -> Option<usize> {
+        if !data.starts_with(PREFIX.as_bytes()) {
+            return None;
+        }
+
+        data[PREFIX.len()..]
+            .windows(SUFFIX.len())
+            .position(|window| window == SUFFIX.as_bytes())
+            .map(|pos| PREFIX.len() + pos + SUFFIX.len())
+    }
+
     pub fn decode(data: &[u8]) -> Result<Self, ProtocolError> {
+        Self::decode_kind(data).map(|(_, response)| response)
+    }
+
+    /// [`decode`](Self::decode), also classifying the envelope. Progress
+    /// envelopes without an `ok` field decode as `ok: true`.
+    pub fn decode_kind(data: &[u8]) -> Result<(ResponseKind, Self), ProtocolError> {
         let s = std::str::from_utf8(data)
             .map_err(|e| ProtocolError::EnvelopeParseError(e.to_string()))?;
 
@@ -209,13 +371,43 @@ impl KittyResponse {
         let msg: serde_json::Value =
             serde_json::from_str(json_str).map_err(ProtocolError::JsonError)?;
 
-        if !msg.is_object() {
+        let serde_json::Value::Object(mut envelope) = msg else {
             return Err(ProtocolError::EnvelopeParseError(
                 "Response is not a JSON object".to_string(),
             ));
+        };
+
+        let kind = ResponseKind::classify(&envelope);
+        if kind == ResponseKind::Progress {
+            envelope
+                .entry("ok")
+                .or_insert(serde_json::Value::Bool(true));
         }
 
-        serde_json::from_value(msg).map_err(ProtocolError::JsonError)
+        let response = serde_json::from_value(serde_json::Value::Object(envelope))
+            .map_err(ProtocolError::JsonError)?;
+        Ok((kind, response))
+    }
+
+    /// Decode a binary blob carried in `data`.
+    ///
+    /// Kitty marks encoded payloads with a `base64:` or `base85:` prefix, the
+    /// same convention `send-text` uses for its `data` field. Returns `None`
+    /// when `data` is absent or is not a marked string.
+    pub fn data_bytes(&self) -> Option<Result<Vec<u8>, ProtocolError>> {
+        let s = self.data.as_ref()?.as_str()?;
+
+        let decoded = if let Some(encoded) = s.strip_prefix("base64:") {
+            base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|e| e.to_string())
+        } else if let Some(encoded) = s.strip_prefix("base85:") {
+            base85::decode(encoded).map_err(|e| e.to_string())
+        } else {
+            return None;
+        };
+
+        Some(decoded.map_err(ProtocolError::PayloadValidationError))
     }
 }
 
@@ -223,6 +415,21 @@ impl KittyResponse {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_message_encode_pretty() {
+        let msg = KittyMessage::new("send-text", vec![0, 14, 2])
+            .payload(serde_json::json!({"data": "text:hi", "match": "id:1"}));
+
+        let compact = msg.encode().unwrap();
+        let pretty = msg.encode_pretty().unwrap();
+        assert_ne!(compact, pretty);
+        assert!(pretty.contains(&b'\n'));
+
+        let from_compact = serde_json::to_value(KittyMessage::decode(&compact).unwrap()).unwrap();
+        let from_pretty = serde_json::to_value(KittyMessage::decode(&pretty).unwrap()).unwrap();
+        assert_eq!(from_compact, from_pretty);
+    }
+
     #[test]
     fn test_message_encode() {
         let msg = KittyMessage::new("ls", vec![0, 14, 2]);
@@ -265,6 +472,107 @@ mod tests {
         assert!(response.data.is_some());
     }
 
+    #[test]
+    fn test_response_kind() {
+        let kind = |json: &str| {
+            let raw = format!("\x1bP@kitty-cmd{}\x1b\\", json);
+            KittyResponse::decode_kind(raw.as_bytes()).unwrap().0
+        };
+
+        assert_eq!(kind(r#"{"ok":true,"data":"x"}"#), ResponseKind::Final);
+        assert_eq!(kind(r#"{"ok":true}"#), ResponseKind::Final);
+        assert_eq!(kind(r#"{"ok":false,"error":"nope"}"#), ResponseKind::Final);
+        assert_eq!(
+            kind(r#"{"ok":false,"error":"nope","async_id":"a1"}"#),
+            ResponseKind::Final
+        );
+        assert_eq!(
+            kind(r#"{"ok":true,"data":"7","async_id":"a1"}"#),
+            ResponseKind::Final
+        );
+        assert_eq!(kind(r#"{"ok":true,"async_id":"a1"}"#), ResponseKind::Ack);
+        assert_eq!(kind(r#"{"ok":true,"stream":true}"#), ResponseKind::Ack);
+        assert_eq!(kind(r#"{"progress":0.5}"#), ResponseKind::Progress);
+        assert_eq!(
+            kind(r#"{"ok":true,"progress":"waiting"}"#),
+            ResponseKind::Progress
+        );
+
+        let raw = b"\x1bP@kitty-cmd{\"progress\":1}\x1b\\";
+        assert!(KittyResponse::decode(raw).unwrap().ok);
+    }
+
+    #[test]
+    fn test_response_frame_len() {
+        let one = b"\x1bP@kitty-cmd{\"ok\":true}\x1b\\";
+        let mut two = one.to_vec();
+        two.extend_from_slice(b"\x1bP@kitty-cmd{}");
+        assert_eq!(KittyResponse::frame_len(one), Some(one.len()));
+        assert_eq!(KittyResponse::frame_len(&two), Some(one.len()));
+        assert_eq!(KittyResponse::frame_len(&two[one.len()..]), None);
+        assert_eq!(KittyResponse::frame_len(b"garbage"), None);
+    }
+
+    #[test]
+    fn test_response_is_complete() {
+        assert!(!KittyResponse::is_complete(b"\x1bP@kit"));
+        assert!(!KittyResponse::is_complete(b"\x1bP@kitty-cmd{\"ok\":true}"));
+        assert!(!KittyResponse::is_complete(b"\x1bP@kitty-cmd"));
+        assert!(KittyResponse::is_complete(
+            b"\x1bP@kitty-cmd{\"ok\":true}\x1b\\"
+        ));
+        assert!(KittyResponse::is_complete(b"garbage"));
+    }
+
+    #[test]
+    fn test_response_data_bytes_base85() {
+        let response = KittyResponse {
+            ok: true,
+            data: Some(serde_json::json!(format!(
+                "base85:{}",
+                base85::encode(&[0u8, 1, 2, 255])
+            ))),
+            error: None,
+        };
+        assert_eq!(
+            response.data_bytes().unwrap().unwrap(),
+            vec![0u8, 1, 2, 255]
+        );
+    }
+
+    #[test]
+    fn test_response_data_bytes_base64() {
+        let response = KittyResponse {
+            ok: true,
+            data: Some(serde_json::json!("base64:aGVsbG8=")),
+            error: None,
+        };
+        assert_eq!(response.data_bytes().unwrap().unwrap(), b"hello".to_vec());
+    }
+
+    #[test]
+    fn test_response_data_bytes_invalid() {
+        let response = KittyResponse {
+            ok: true,
+            data: Some(serde_json::json!("base64:not valid!")),
+            error: None,
+        };
+        assert!(matches!(
+            response.data_bytes(),
+            Some(Err(ProtocolError::PayloadValidationError(_)))
+        ));
+    }
+
+    #[test]
+    fn test_response_data_bytes_plain_string() {
+        let response = KittyResponse {
+            ok: true,
+            data: Some(serde_json::json!("just some text")),
+            error: None,
+        };
+        assert!(response.data_bytes().is_none());
+    }
+
     #[test]
     fn test_async_id() {
         let msg = KittyMessage::new("select-window", vec![0, 14, 2]).async_id("abc123");
@@ -313,6 +621,64 @@ mod tests {
         assert_eq!(chunks.len(), 1);
     }
 
+    #[test]
+    fn test_into_chunks_send_text_commands() {
+        // Three-byte chars, so 4096-byte splits would cut through one.
+        let text = "\u{20ac}".repeat(3000);
+        let msg = KittyMessage::new("send-text", vec![0, 14, 2]).payload(serde_json::json!({
+            "data": format!("text:{}", text),
+            "match": "id:3",
+            "exclude_active": true,
+        }));
+        let chunks = msg.into_chunks();
+        assert_eq!(chunks.len(), 3);
+
+        let mut joined = String::new();
+        for chunk in &chunks {
+            assert!(chunk.stream_id.is_none());
+            let payload = chunk.payload.as_ref().unwrap();
+            assert_eq!(payload["match"], "id:3");
+            assert_eq!(payload["exclude_active"], true);
+            let data = payload["data"].as_str().unwrap();
+            assert!(data.len() <= MAX_CHUNK_SIZE);
+            joined.push_str(data.strip_prefix("text:").unwrap());
+        }
+        assert_eq!(joined, text);
+
+        let encoded = "QUJD".repeat(2000);
+        let msg = KittyMessage::new("send-text", vec![0, 14, 2])
+            .payload(serde_json::json!({"data": format!("base64:{}", encoded)}));
+        for chunk in msg.into_chunks() {
+            let data = chunk.payload.unwrap()["data"].as_str().unwrap().to_string();
+            let piece = data.strip_prefix("base64:").unwrap();
+            assert_eq!(piece.len() % 4, 0);
+        }
+    }
+
+    #[test]
+    fn test_into_chunks_keeps_payload() {
+        let data = "QUJD".repeat(2000);
+        let msg = KittyMessage::new("set-window-logo", vec![0, 14, 2]).payload(serde_json::json!({
+            "data": data,
+            "match": "id:3",
+            "position": "top-left",
+            "alpha": 0.5,
+        }));
+        let chunks = msg.into_chunks();
+        assert_eq!(chunks.len(), 3);
+
+        let mut joined = String::new();
+        for chunk in &chunks {
+            let payload = chunk.payload.as_ref().unwrap();
+            assert_eq!(payload["match"], "id:3");
+            assert_eq!(payload["position"], "top-left");
+            assert_eq!(payload["alpha"], 0.5);
+            joined.push_str(payload["data"].as_str().unwrap());
+        }
+        assert_eq!(joined, data);
+        assert_eq!(chunks[2].payload.as_ref().unwrap()["data"], "");
+    }
+
     #[test]
     fn test_into_chunks_with_streaming() {
         let large_data = "x".repeat(5000);